@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::Container;
+
+/// ARTIFACT_ENV names the JSONL file every started and stopped container is appended to.
+const ARTIFACT_ENV: &str = "DOCKER_TESTER_ARTIFACT";
+
+/// Event is a line of the artifact file.
+#[derive(Debug, Serialize)]
+struct Event<'a> {
+    event: &'a str,
+    id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<&'a str>,
+    /// the container ports by the mapped host ports.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    ports: BTreeMap<u16, u16>,
+    /// the milliseconds since the unix epoch.
+    timestamp: u128,
+    status: String,
+}
+
+/// appends the start of the container when DOCKER_TESTER_ARTIFACT is set.
+pub(crate) fn record_start(container: &Container, status: &str) {
+    record(Event {
+        event: "start",
+        id: &container.id,
+        image: Some(&container.image),
+        ports: container.ports.iter().map(|(k, v)| (*k, *v)).collect(),
+        timestamp: now_millis(),
+        status: status.to_string(),
+    });
+}
+
+/// appends the stop of the container when DOCKER_TESTER_ARTIFACT is set,
+/// the status is the error when it failed.
+pub(crate) fn record_stop(id: &str, event: &str, result: &Result<(), anyhow::Error>) {
+    record(Event {
+        event,
+        id,
+        image: None,
+        ports: BTreeMap::new(),
+        timestamp: now_millis(),
+        status: match result {
+            Ok(()) => "ok".to_string(),
+            Err(err) => err.to_string().trim().to_string(),
+        },
+    });
+}
+
+fn record(event: Event) {
+    let Ok(path) = std::env::var(ARTIFACT_ENV) else {
+        return;
+    };
+    // a broken artifact file must not fail the test, it's only for debugging
+    if let Err(err) = append(&path, &event) {
+        warn!("Container[{}] not recorded to {path}: {err}", event.id);
+    }
+}
+
+fn append(path: &str, event: &Event) -> Result<(), anyhow::Error> {
+    let mut line = serde_json::to_string(event)?;
+    line.push('\n');
+    // a single write of an O_APPEND file keeps the lines of parallel tests whole
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())?;
+    Ok(())
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn append_should_write_a_json_line() {
+        let path =
+            std::env::temp_dir().join(format!("docker-tester-{}.jsonl", uuid::Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        let event = Event {
+            event: "start",
+            id: "dfd60e4ef0c0",
+            image: Some("redis:7-alpine"),
+            ports: BTreeMap::from([(6379, 49153)]),
+            timestamp: 1,
+            status: "running".to_string(),
+        };
+        append(path, &event).unwrap();
+        append(path, &event).unwrap();
+        let lines = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let line = r#"{"event":"start","id":"dfd60e4ef0c0","image":"redis:7-alpine","ports":{"6379":49153},"timestamp":1,"status":"running"}"#;
+        assert_eq!(lines, format!("{line}\n{line}\n"));
+    }
+}
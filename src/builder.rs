@@ -0,0 +1,167 @@
+use std::path::Path;
+
+use crate::{start_container_with_opts, Container, StartOptions};
+
+/// ContainerBuilder collects the image, port, args and StartOptions of a
+/// container, so the configuration can be validated before starting it.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::ContainerBuilder;
+///
+/// let container = ContainerBuilder::new("postgres:14-alpine")
+///     .port("5432")
+///     .args(["-e", "POSTGRES_PASSWORD=password"])
+///     .start()
+///     .expect("Failed to start Postgres container");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ContainerBuilder {
+    image: String,
+    port: String,
+    args: Vec<String>,
+    opts: StartOptions,
+}
+
+impl ContainerBuilder {
+    /// creates a ContainerBuilder for the image.
+    pub fn new(image: impl Into<String>) -> Self {
+        Self {
+            image: image.into(),
+            ..Default::default()
+        }
+    }
+
+    /// sets the container port to publish and connect to.
+    pub fn port(mut self, port: impl Into<String>) -> Self {
+        self.port = port.into();
+        self
+    }
+
+    /// appends an arg passed to `docker run` before the image name.
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// appends args passed to `docker run` before the image name.
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// sets the StartOptions of the container.
+    pub fn opts(mut self, opts: StartOptions) -> Self {
+        self.opts = opts;
+        self
+    }
+
+    /// checks the configuration is coherent without invoking docker,
+    /// returning the first problem found.
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.image.trim().is_empty() {
+            return Err(anyhow::anyhow!("the image is not set"));
+        }
+        if self.port.parse::<u16>().is_err() {
+            return Err(anyhow::anyhow!("invalid port {:?}", self.port));
+        }
+        let args: Vec<&str> = self.args.iter().map(String::as_str).collect();
+        for host_path in bind_mount_host_paths(&args) {
+            if !Path::new(host_path).exists() {
+                return Err(anyhow::anyhow!(
+                    "the volume host path {host_path} doesn't exist"
+                ));
+            }
+        }
+        self.opts.validate()
+    }
+
+    /// validates the configuration and starts the container.
+    pub fn start(self) -> Result<Container, anyhow::Error> {
+        self.validate()?;
+        let args: Vec<&str> = self.args.iter().map(String::as_str).collect();
+        start_container_with_opts(&self.image, &self.port, &args, self.opts)
+    }
+}
+
+/// gets the host paths of the bind mounts among the `docker run` args.
+pub(crate) fn bind_mount_host_paths<'a>(args: &[&'a str]) -> Vec<&'a str> {
+    let mut host_paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let volume = match *arg {
+            "-v" | "--volume" => args.next().copied(),
+            arg => arg.strip_prefix("--volume="),
+        };
+        host_paths.extend(volume.and_then(bind_mount_host_path));
+    }
+    host_paths
+}
+
+/// gets the host path of a `host:container[:mode]` volume, None for a named volume.
+fn bind_mount_host_path(volume: &str) -> Option<&str> {
+    let (source, _) = volume.split_once(':')?;
+    (source.starts_with('/') || source.starts_with('.')).then_some(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_mount_host_paths_should_skip_named_volumes() {
+        let args = [
+            "-v",
+            "/fixtures:/data",
+            "--volume",
+            "cache:/cache",
+            "--volume=./seed:/seed:ro",
+            "-e",
+            "A=/b:c",
+        ];
+        assert_eq!(bind_mount_host_paths(&args), ["/fixtures", "./seed"]);
+    }
+
+    #[test]
+    fn validate_should_return_the_first_problem() {
+        let err = ContainerBuilder::default().validate().unwrap_err();
+        assert_eq!(err.to_string(), "the image is not set");
+
+        let builder = ContainerBuilder::new("redis:7-alpine");
+        let err = builder.clone().validate().unwrap_err();
+        assert_eq!(err.to_string(), r#"invalid port """#);
+
+        let builder = builder.port("6379");
+        builder.validate().unwrap();
+        builder
+            .clone()
+            .args(["-v", "cache:/data"])
+            .validate()
+            .unwrap();
+        builder
+            .clone()
+            .args(["-v", ".:/src:ro"])
+            .validate()
+            .unwrap();
+        let err = builder
+            .clone()
+            .arg("--volume=/does/not/exist:/data")
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the volume host path /does/not/exist doesn't exist"
+        );
+
+        let opts = StartOptions {
+            platform: Some("linux".to_string()),
+            ..Default::default()
+        };
+        assert!(builder.opts(opts).validate().is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use sqlx::{migrate::Migrator, Connection, Executor, PgConnection, PgPool};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::{path::Path, time};
+use uuid::Uuid;
+
+use crate::wait::{readiness_timeout, Backoff};
+use crate::{
+    start_container_ports_async, stop_container, DockerTesterError, RemovalMode, StartOptions,
+};
+
+/// TestCockroach contains a CockroachDB connection infomation.
+///
+/// CockroachDB speaks the Postgres wire protocol, so the urls and pools are
+/// the same as the ones of TestPostgres.
+pub struct TestCockroach {
+    pub host: String,
+    pub port: u16,
+    pub http_port: u16,
+    pub user: String,
+    pub dbname: String,
+    pub container_id: String,
+    removal_mode: RemovalMode,
+}
+
+impl TestCockroach {
+    /// creates a TestCockroach running a single insecure node.
+    pub async fn new(migration_path: impl Into<String>) -> Result<Self, anyhow::Error> {
+        let dbname = format!("test_cockroach_{}", Uuid::new_v4());
+        let image = "cockroachdb/cockroach:latest";
+        let port = "26257";
+        let opts = StartOptions {
+            command: vec!["start-single-node".to_string(), "--insecure".to_string()],
+            ..Default::default()
+        };
+        let container = start_container_ports_async(image, &[port, "8080"], &[], opts)
+            .await
+            .map_err(DockerTesterError::from_start)?;
+        let http_port = container
+            .host_port(8080)
+            .ok_or_else(|| anyhow::anyhow!("the CockroachDB http port 8080 is not mapped"))?;
+        let test_cockroach = Self {
+            dbname: dbname.clone(),
+            container_id: container.id,
+            host: container.host,
+            port: container.port,
+            http_port,
+            user: "root".to_string(),
+            removal_mode: RemovalMode::default(),
+        };
+
+        // the sql port opens before the node accepts queries, so wait for both
+        let mut backoff = Backoff::new(readiness_timeout(None)?);
+        let mut conn = loop {
+            let (host, http_port) = (test_cockroach.host.clone(), test_cockroach.http_port);
+            let ready = tokio::task::spawn_blocking(move || is_http_ready(&host, http_port))
+                .await
+                .unwrap_or(false);
+            let err = match PgConnection::connect(&test_cockroach.server_url()).await {
+                Ok(conn) if ready => {
+                    info!("CockroachDB is ready to go");
+                    break conn;
+                }
+                Ok(conn) => {
+                    conn.close().await?;
+                    anyhow::anyhow!("CockroachDB did not report ready")
+                }
+                Err(err) => DockerTesterError::Connect(err).into(),
+            };
+            match backoff.next_delay() {
+                Some(delay) => {
+                    debug!("CockroachDB is not ready");
+                    tokio::time::sleep(delay).await;
+                }
+                None => return Err(err),
+            }
+        };
+        conn.execute(format!(r#"CREATE DATABASE "{}";"#, dbname).as_str())
+            .await
+            .map_err(DockerTesterError::CreateDatabase)?;
+        conn.close().await?;
+        info!("CockroachDB created database {}", dbname);
+
+        let db_pool = PgPool::connect(&test_cockroach.url())
+            .await
+            .map_err(DockerTesterError::Connect)?;
+        let mut m = Migrator::new(Path::new(&migration_path.into()))
+            .await
+            .map_err(DockerTesterError::Migrate)?;
+        // CockroachDB has no advisory locks
+        m.set_locking(false);
+        m.run(&db_pool).await.map_err(DockerTesterError::Migrate)?;
+        info!("CockroachDB database {} migrated", dbname);
+        db_pool.close().await;
+
+        Ok(test_cockroach)
+    }
+
+    /// gets a CockroachDB db pool.
+    pub async fn get_pool(&self) -> PgPool {
+        sqlx::postgres::PgPoolOptions::default()
+            .max_connections(5)
+            .connect(&self.url())
+            .await
+            .unwrap()
+    }
+
+    /// decides whether the container is removed on drop, defaults to RemovalMode::Always.
+    pub fn removal_mode(mut self, removal_mode: RemovalMode) -> Self {
+        self.removal_mode = removal_mode;
+        self
+    }
+
+    pub fn server_url(&self) -> String {
+        format!(
+            "postgres://{}@{}:{}/defaultdb?sslmode=disable",
+            self.user, self.host, self.port
+        )
+    }
+
+    pub fn url(&self) -> String {
+        format!(
+            "postgres://{}@{}:{}/{}?sslmode=disable",
+            self.user, self.host, self.port, self.dbname
+        )
+    }
+}
+
+impl Drop for TestCockroach {
+    fn drop(&mut self) {
+        if !self.removal_mode.should_remove() {
+            info!(
+                "CockroachDB container {} kept, connect with {}",
+                self.container_id,
+                self.url()
+            );
+            return;
+        }
+        // a panic while panicking aborts, so only report the failure
+        match stop_container(self.container_id.clone()) {
+            Ok(()) => info!("CockroachDB container {} dropped", self.container_id),
+            Err(err) => warn!(
+                "CockroachDB container {} cannot be stopped: {err}",
+                self.container_id
+            ),
+        }
+    }
+}
+
+/// asks the `/health?ready=1` endpoint whether the node accepts queries.
+fn is_http_ready(host: &str, http_port: u16) -> bool {
+    let Ok(mut stream) = TcpStream::connect((host, http_port)) else {
+        return false;
+    };
+    let _ = stream.set_read_timeout(Some(time::Duration::from_secs(5)));
+    let request = format!("GET /health?ready=1 HTTP/1.0\r\nHost: {host}\r\n\r\n");
+    if stream.write_all(request.as_bytes()).is_err() {
+        return false;
+    }
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+    is_ok_response(&response)
+}
+
+fn is_ok_response(response: &str) -> bool {
+    response
+        .lines()
+        .next()
+        .and_then(|status| status.split_whitespace().nth(1))
+        == Some("200")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_ok_response_should_read_the_status_line() {
+        assert!(is_ok_response("HTTP/1.0 200 OK\r\n\r\n{}"));
+        assert!(!is_ok_response("HTTP/1.0 503 Service Unavailable\r\n\r\n"));
+        assert!(!is_ok_response(""));
+    }
+
+    #[tokio::test]
+    async fn test_cockroach_should_create_and_drop() {
+        let test_cockroach = TestCockroach::new("./migrations").await.unwrap();
+        let pool = test_cockroach.get_pool().await;
+        sqlx::query("INSERT INTO todos (title) VALUES ('test')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let (title,) = sqlx::query_as::<_, (String,)>("SELECT title FROM todos")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(title, "test");
+    }
+}
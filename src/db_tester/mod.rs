@@ -0,0 +1,17 @@
+#[cfg(feature = "cockroach")]
+mod cockroach;
+#[cfg(feature = "mssql")]
+mod mssql;
+#[cfg(feature = "mysql")]
+mod mysql;
+mod postgres;
+mod redis;
+
+#[cfg(feature = "cockroach")]
+pub use cockroach::TestCockroach;
+#[cfg(feature = "mssql")]
+pub use mssql::TestMssql;
+#[cfg(feature = "mysql")]
+pub use mysql::{start_mysql, TestMySQL};
+pub use postgres::{start_postgres, TestPostgres, TestPostgresBuilder};
+pub use redis::TestRedis;
@@ -0,0 +1,230 @@
+use sqlx::{
+    migrate::Migrator, mysql::MySqlPoolOptions, Connection, Executor, MySqlConnection, MySqlPool,
+};
+use std::collections::BTreeMap;
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::wait::{readiness_timeout, Backoff};
+use crate::{
+    start_container_with_opts_async, stop_container, Container, ContainerGuard, DockerTesterError,
+    RemovalMode, StartOptions,
+};
+
+/// DEFAULT_IMAGE is the image TestMySQL::new starts.
+const DEFAULT_IMAGE: &str = "mysql:8";
+
+/// TestMySQL contains a MySQL db connection infomation.
+pub struct TestMySQL {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub password: String,
+    pub dbname: String,
+    pub container_id: String,
+    removal_mode: RemovalMode,
+    container: Container,
+    /// true once the container is handed to a ContainerGuard, drop leaves it alone then.
+    guarded: bool,
+}
+
+impl TestMySQL {
+    /// creates a TestMySQL from `mysql:8`, with a migrated random database.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::TestMySQL;
+    ///
+    /// # async fn run() {
+    /// let test_mysql = TestMySQL::new("./migrations").await.unwrap();
+    /// let pool = test_mysql.get_pool().await;
+    /// # }
+    /// ```
+    pub async fn new(migration_path: impl Into<String>) -> Result<Self, anyhow::Error> {
+        Self::with_image(DEFAULT_IMAGE, migration_path).await
+    }
+
+    /// creates a TestMySQL from another MySQL image, e.g. `mysql:5.7`.
+    pub async fn with_image(
+        image: &str,
+        migration_path: impl Into<String>,
+    ) -> Result<Self, anyhow::Error> {
+        let user = "root".to_string();
+        let password = "password".to_string();
+        let dbname = format!("test_mysql_{}", Uuid::new_v4().simple());
+        let port = "3306";
+        let opts = StartOptions {
+            env: BTreeMap::from([("MYSQL_ROOT_PASSWORD".to_string(), password.clone())]),
+            ..Default::default()
+        };
+        let container = start_container_with_opts_async(image, port, &[], opts)
+            .await
+            .map_err(DockerTesterError::from_start)?;
+        let test_mysql = Self {
+            dbname: dbname.clone(),
+            container_id: container.id.clone(),
+            host: container.host.clone(),
+            port: container.port,
+            user,
+            password,
+            container,
+            guarded: false,
+            removal_mode: RemovalMode::default(),
+        };
+
+        // the entrypoint's init server skips networking, so a tcp connection
+        // only succeeds once the real server is up
+        let mut backoff = Backoff::new(readiness_timeout(None)?);
+        let mut conn = loop {
+            match MySqlConnection::connect(&test_mysql.server_url()).await {
+                Ok(conn) => {
+                    info!("MySQL is ready to go");
+                    break conn;
+                }
+                Err(err) => match backoff.next_delay() {
+                    Some(delay) => {
+                        debug!("MySQL is not ready");
+                        tokio::time::sleep(delay).await;
+                    }
+                    None => return Err(DockerTesterError::Connect(err).into()),
+                },
+            }
+        };
+        conn.execute(format!("CREATE DATABASE `{dbname}`;").as_str())
+            .await
+            .map_err(DockerTesterError::CreateDatabase)?;
+        conn.close().await?;
+        info!("MySQL created database {}", dbname);
+
+        let db_pool = MySqlPool::connect(&test_mysql.url())
+            .await
+            .map_err(DockerTesterError::Connect)?;
+        let m = Migrator::new(Path::new(&migration_path.into()))
+            .await
+            .map_err(DockerTesterError::Migrate)?;
+        m.run(&db_pool).await.map_err(DockerTesterError::Migrate)?;
+        info!("MySQL database {} migrated", dbname);
+        db_pool.close().await;
+
+        Ok(test_mysql)
+    }
+
+    /// gets a MySQL db pool.
+    pub async fn get_pool(&self) -> MySqlPool {
+        MySqlPoolOptions::default()
+            .max_connections(5)
+            .connect(&self.url())
+            .await
+            .unwrap()
+    }
+
+    /// decides whether the container is removed on drop, defaults to RemovalMode::Always.
+    pub fn removal_mode(mut self, removal_mode: RemovalMode) -> Self {
+        self.removal_mode = removal_mode;
+        self
+    }
+
+    /// gets the container the database runs in.
+    pub fn container(&self) -> &Container {
+        &self.container
+    }
+
+    /// hands the container over to a ContainerGuard.
+    fn into_guard(mut self) -> ContainerGuard {
+        self.guarded = true;
+        ContainerGuard::new(self.container.clone()).removal_mode(self.removal_mode)
+    }
+
+    pub fn server_url(&self) -> String {
+        format!(
+            "mysql://{}:{}@{}:{}",
+            self.user, self.password, self.host, self.port
+        )
+    }
+
+    pub fn url(&self) -> String {
+        format!("{}/{}", self.server_url(), self.dbname)
+    }
+}
+
+/// Starts a MySQL container with a migrated database like TestMySQL::new,
+/// and returns a pool to it with a guard stopping the container on drop.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::start_mysql;
+///
+/// # async fn run() {
+/// let (pool, _guard) = start_mysql("./migrations").await.unwrap();
+/// sqlx::query("SELECT 1").execute(&pool).await.unwrap();
+/// // the container is removed when _guard is dropped
+/// # }
+/// ```
+pub async fn start_mysql(
+    migration_path: impl Into<String>,
+) -> Result<(MySqlPool, ContainerGuard), anyhow::Error> {
+    let test_mysql = TestMySQL::new(migration_path).await?;
+    let pool = test_mysql.get_pool().await;
+    Ok((pool, test_mysql.into_guard()))
+}
+
+impl Drop for TestMySQL {
+    fn drop(&mut self) {
+        if self.guarded {
+            return;
+        }
+        if !self.removal_mode.should_remove() {
+            info!(
+                "MySQL container {} kept, connect with {}",
+                self.container_id,
+                self.url()
+            );
+            return;
+        }
+        // a panic while panicking aborts, so only report the failure
+        match stop_container(self.container_id.clone()) {
+            Ok(()) => info!("MySQL container {} dropped", self.container_id),
+            Err(err) => warn!(
+                "MySQL container {} cannot be stopped: {err}",
+                self.container_id
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mysql_should_create_and_drop() {
+        let test_mysql = TestMySQL::new("./migrations").await.unwrap();
+        let pool = test_mysql.get_pool().await;
+        sqlx::query("INSERT INTO todos (title) VALUES ('test')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let (title,) = sqlx::query_as::<_, (String,)>("SELECT title FROM todos")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(title, "test");
+    }
+
+    #[tokio::test]
+    async fn start_mysql_should_stop_the_container_with_the_guard() {
+        let (pool, guard) = start_mysql("./migrations").await.unwrap();
+        sqlx::query("INSERT INTO todos (title) VALUES ('test')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let id = guard.id.clone();
+        drop(guard);
+        assert!(!crate::list_containers()
+            .unwrap()
+            .iter()
+            .any(|container| id.starts_with(&container.id)));
+    }
+}
@@ -0,0 +1,1893 @@
+use sqlx::{
+    migrate::{MigrateError, Migrator},
+    postgres::{PgConnectOptions, PgPoolOptions},
+    Connection, Executor, PgConnection, PgPool,
+};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::{fs, time};
+use tokio::runtime::RuntimeFlavor;
+use uuid::Uuid;
+
+mod replica;
+
+use crate::wait::{exec_output, exec_succeeds, readiness_timeout, Backoff};
+use crate::{
+    container_logs, remote_docker_host, start_container_with_opts_async, stop_container_with_opts,
+    Container, ContainerGuard, DockerTesterError, RemovalMode, StartOptions, StopOptions, LABEL,
+};
+
+/// TestPostgres contains a db connection infomation.
+pub struct TestPostgres {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub password: String,
+    pub dbname: String,
+    pub container_id: String,
+    /// the role owning dbname, url connects as this role when it's set.
+    pub owner: Option<String>,
+    /// the time from starting the container to the first successful query against
+    /// the migrated database, including the pull, the boot and the migrations.
+    pub setup_duration: time::Duration,
+    owner_password: String,
+    after_connect: Option<String>,
+    container: Container,
+    replica: Option<replica::Replica>,
+    /// the options the container was started with, a replica starts with them too.
+    start_options: StartOptions,
+    stop_opts: StopOptions,
+    removal_mode: RemovalMode,
+    url_params: Vec<(String, String)>,
+    /// false for a fork, which only drops its database and leaves the container running.
+    owns_container: bool,
+    /// true once the container is handed to a ContainerGuard, drop leaves it alone then.
+    guarded: bool,
+    migration_schema: Option<String>,
+    pool_size: Option<u32>,
+    acquire_timeout: Option<time::Duration>,
+    statement_timeout: Option<time::Duration>,
+    /// the pools handed out, closed before the container is stopped.
+    pools: Mutex<Vec<PgPool>>,
+    /// names the forks, shared with them so a seed keeps them reproducible.
+    names: Arc<Mutex<NameSource>>,
+}
+
+/// CREATED_DATABASES has the names of the databases this process created, see assert_clean.
+static CREATED_DATABASES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn track_database(dbname: &str) {
+    CREATED_DATABASES
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .push(dbname.to_string());
+}
+
+/// PGVECTOR_IMAGE is the image TestPostgres::with_pgvector runs.
+const PGVECTOR_IMAGE: &str = "pgvector/pgvector:pg16";
+
+/// ALPHANUMERIC is the default charset of the generated passwords.
+const ALPHANUMERIC: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// CreateDatabaseFn builds the CREATE DATABASE statement from the database name.
+type CreateDatabaseFn = dyn Fn(&str) -> String + Send + Sync;
+
+/// ReadyWhenFn tells whether the migrated database is in the state the test needs.
+type ReadyWhenFn = dyn for<'a> Fn(&'a PgPool) -> ReadyFuture<'a> + Send + Sync;
+
+/// ReadyFuture is the future returned by a ready_when closure.
+type ReadyFuture<'a> = Pin<Box<dyn Future<Output = Result<bool, anyhow::Error>> + Send + 'a>>;
+
+/// TestPostgresBuilder configures how a TestPostgres creates its database.
+pub struct TestPostgresBuilder {
+    migration_path: Option<String>,
+    encoding: Option<String>,
+    lc_collate: Option<String>,
+    lc_ctype: Option<String>,
+    template: Option<String>,
+    keep_volumes: bool,
+    recreate_on_mismatch: bool,
+    owner: Option<String>,
+    exec_readiness: bool,
+    after_connect: Option<String>,
+    removal_mode: RemovalMode,
+    timeout: Option<time::Duration>,
+    url_params: Vec<(String, String)>,
+    max_connections: Option<u32>,
+    migration_schema: Option<String>,
+    config_file: Option<PathBuf>,
+    image: String,
+    user: Option<String>,
+    password: Option<String>,
+    dbname: Option<String>,
+    pool_size: Option<u32>,
+    acquire_timeout: Option<time::Duration>,
+    statement_timeout: Option<time::Duration>,
+    create_database: Option<Box<CreateDatabaseFn>>,
+    create_database_retries: u32,
+    pg_args: Vec<String>,
+    reuse: bool,
+    ready_when: Option<Box<ReadyWhenFn>>,
+    seeds: Vec<Seed>,
+    password_charset: String,
+    password_length: usize,
+    extensions: Vec<String>,
+    timezone: String,
+    start_options: StartOptions,
+    rng_seed: Option<u64>,
+}
+
+/// Seed is SQL run once after the migrations.
+enum Seed {
+    Sql(String),
+    File(PathBuf),
+}
+
+impl Default for TestPostgresBuilder {
+    fn default() -> Self {
+        Self {
+            migration_path: Some("./migrations".to_string()),
+            encoding: None,
+            lc_collate: None,
+            lc_ctype: None,
+            template: None,
+            keep_volumes: false,
+            recreate_on_mismatch: false,
+            owner: None,
+            exec_readiness: false,
+            after_connect: None,
+            removal_mode: RemovalMode::default(),
+            timeout: None,
+            url_params: Vec::new(),
+            max_connections: None,
+            migration_schema: None,
+            config_file: None,
+            image: DEFAULT_IMAGE.to_string(),
+            user: None,
+            password: None,
+            dbname: None,
+            pool_size: None,
+            acquire_timeout: None,
+            statement_timeout: None,
+            create_database: None,
+            create_database_retries: 5,
+            pg_args: Vec::new(),
+            reuse: false,
+            ready_when: None,
+            seeds: Vec::new(),
+            password_charset: ALPHANUMERIC.to_string(),
+            password_length: 32,
+            extensions: Vec::new(),
+            timezone: "UTC".to_string(),
+            start_options: StartOptions::default(),
+            rng_seed: None,
+        }
+    }
+}
+
+impl TestPostgresBuilder {
+    /// sets the directory of the sqlx migrations, defaults to `./migrations`.
+    /// An empty path skips the migrations, like without_migrations.
+    pub fn migration_path(mut self, migration_path: impl Into<String>) -> Self {
+        self.migration_path = Some(migration_path.into()).filter(|path| !path.is_empty());
+        self
+    }
+
+    /// skips the migrations, leaving the created database empty, e.g. to test
+    /// a schema-creation routine itself.
+    pub fn without_migrations(mut self) -> Self {
+        self.migration_path = None;
+        self
+    }
+
+    /// sets the Postgres image, defaults to `postgres:14-alpine`,
+    /// e.g. `postgres:16-alpine` to match the version used in production.
+    pub fn image(mut self, image: impl Into<String>) -> Self {
+        self.image = image.into();
+        self
+    }
+
+    /// sets the superuser of the server, defaults to a random name.
+    pub fn user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// sets the password of the superuser, defaults to a random one.
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// sets the name of the created database, defaults to a random name,
+    /// for migrations that assume a specific database.
+    pub fn dbname(mut self, dbname: impl Into<String>) -> Self {
+        self.dbname = Some(dbname.into());
+        self
+    }
+
+    /// sets the size of the pools from get_pool, which wins over the
+    /// `DOCKER_TESTER_PG_POOL_SIZE` env var. Unlike max_connections, it
+    /// doesn't change the server.
+    pub fn pool_size(mut self, pool_size: u32) -> Self {
+        self.pool_size = Some(pool_size);
+        self
+    }
+
+    /// bounds how long the pools from get_pool wait for a free connection,
+    /// sqlx waits 30 seconds by default, so a test holding every connection
+    /// fails fast instead of looking frozen.
+    pub fn acquire_timeout(mut self, acquire_timeout: time::Duration) -> Self {
+        self.acquire_timeout = Some(acquire_timeout);
+        self
+    }
+
+    /// sets `statement_timeout` on every connection of the pools from get_pool,
+    /// so a hung query fails with "canceling statement due to statement timeout".
+    pub fn statement_timeout(mut self, statement_timeout: time::Duration) -> Self {
+        self.statement_timeout = Some(statement_timeout);
+        self
+    }
+
+    /// sets the `ENCODING` of the created database, e.g. `UTF8`.
+    pub fn encoding(mut self, encoding: impl Into<String>) -> Self {
+        self.encoding = Some(encoding.into());
+        self
+    }
+
+    /// sets the `LC_COLLATE` of the created database, e.g. `en_US.utf8`.
+    pub fn lc_collate(mut self, lc_collate: impl Into<String>) -> Self {
+        self.lc_collate = Some(lc_collate.into());
+        self
+    }
+
+    /// sets the `LC_CTYPE` of the created database, e.g. `en_US.utf8`.
+    pub fn lc_ctype(mut self, lc_ctype: impl Into<String>) -> Self {
+        self.lc_ctype = Some(lc_ctype.into());
+        self
+    }
+
+    /// sets the `TEMPLATE` of the created database.
+    ///
+    /// When a locale is given without a template, `template0` is used, because
+    /// Postgres rejects a locale that differs from the one of `template1`.
+    pub fn template(mut self, template: impl Into<String>) -> Self {
+        self.template = Some(template.into());
+        self
+    }
+
+    /// sets the characters of the generated passwords, alphanumeric by default.
+    /// They must be URL-safe (`A-Z a-z 0-9 - . _ ~`), so url needs no encoding.
+    pub fn password_charset(mut self, charset: impl Into<String>) -> Self {
+        self.password_charset = charset.into();
+        self
+    }
+
+    /// sets the length of the generated passwords, 32 by default.
+    pub fn password_length(mut self, length: usize) -> Self {
+        self.password_length = length;
+        self
+    }
+
+    /// creates the extension in the database before the migrations, as the
+    /// superuser, e.g. `vector` or `pg_trgm`. The image must ship it.
+    pub fn extension(mut self, extension: impl Into<String>) -> Self {
+        self.extensions.push(extension.into());
+        self
+    }
+
+    /// runs the statements once after the migrations, e.g. to insert the baseline
+    /// rows every test assumes. All the seeds run in a single transaction, in
+    /// the order they're added, and a failing statement fails build.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::TestPostgres;
+    ///
+    /// # async fn run() {
+    /// let test_postgres = TestPostgres::builder()
+    ///     .seed("INSERT INTO todos (title) VALUES ('first'), ('second');")
+    ///     .build()
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn seed(mut self, sql: impl Into<String>) -> Self {
+        self.seeds.push(Seed::Sql(sql.into()));
+        self
+    }
+
+    /// runs the statements of the file once after the migrations, like seed.
+    pub fn seed_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.seeds.push(Seed::File(path.into()));
+        self
+    }
+
+    /// waits after the migrations until ready_when returns true, e.g. until a
+    /// materialized view is populated or a background job inserted its row.
+    /// It's polled every 200ms within the readiness timeout.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::TestPostgres;
+    ///
+    /// # async fn run() {
+    /// let test_postgres = TestPostgres::builder()
+    ///     .ready_when(|pool| {
+    ///         Box::pin(async move {
+    ///             let (count,): (i64,) = sqlx::query_as("SELECT count(*) FROM todos")
+    ///                 .fetch_one(pool)
+    ///                 .await?;
+    ///             Ok(count > 0)
+    ///         })
+    ///     })
+    ///     .build()
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn ready_when<F>(mut self, ready_when: F) -> Self
+    where
+        F: for<'a> Fn(&'a PgPool) -> ReadyFuture<'a> + Send + Sync + 'static,
+    {
+        self.ready_when = Some(Box::new(ready_when));
+        self
+    }
+
+    /// reuses a running Postgres container started by an earlier tester with
+    /// the same configuration, found by its labels, instead of starting one.
+    /// Each tester still creates its own database, and only drops that database,
+    /// the shared container keeps running for the next tests. The user and the
+    /// password default to fixed values then, so the configurations match, and
+    /// it can't be combined with owner.
+    pub fn reuse(mut self, reuse: bool) -> Self {
+        self.reuse = reuse;
+        self
+    }
+
+    /// keeps the data volume of the container when it's dropped, so it can be
+    /// inspected after a failed test.
+    pub fn keep_volumes(mut self, keep_volumes: bool) -> Self {
+        self.keep_volumes = keep_volumes;
+        self
+    }
+
+    /// drops and recreates the database when an applied migration was changed
+    /// or removed, instead of failing with a stale database error. A database
+    /// named with dbname that already exists, e.g. in a reused container or a
+    /// kept volume, is migrated instead of created then.
+    pub fn recreate_on_mismatch(mut self, recreate_on_mismatch: bool) -> Self {
+        self.recreate_on_mismatch = recreate_on_mismatch;
+        self
+    }
+
+    /// sets how many times CREATE DATABASE is retried while another session
+    /// uses the template, defaults to 5. The n-th retry waits n * 100ms.
+    pub fn create_database_retries(mut self, retries: u32) -> Self {
+        self.create_database_retries = retries;
+        self
+    }
+
+    /// creates a non-superuser role owning the database, migrations and pools
+    /// then connect as this role like an application would in production.
+    pub fn owner(mut self, owner: impl Into<String>) -> Self {
+        self.owner = Some(owner.into());
+        self
+    }
+
+    /// replaces the generated CREATE DATABASE statement, the closure gets the
+    /// database name and returns the whole statement, e.g. to set a tablespace
+    /// or a connection limit. encoding, lc_collate, lc_ctype, template and owner
+    /// are ignored then.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::TestPostgres;
+    ///
+    /// # async fn run() {
+    /// let test_postgres = TestPostgres::builder()
+    ///     .create_database(|dbname| format!(r#"CREATE DATABASE "{dbname}" CONNECTION LIMIT 10;"#))
+    ///     .build()
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn create_database(
+        mut self,
+        create_database: impl Fn(&str) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.create_database = Some(Box::new(create_database));
+        self
+    }
+
+    /// checks readiness with `pg_isready` inside the container instead of
+    /// connecting from the host. It's always used for a remote `DOCKER_HOST`.
+    pub fn exec_readiness(mut self, exec_readiness: bool) -> Self {
+        self.exec_readiness = exec_readiness;
+        self
+    }
+
+    /// runs the sql on every new connection of the pools from get_pool,
+    /// e.g. `SET search_path TO app`.
+    pub fn after_connect(mut self, sql: impl Into<String>) -> Self {
+        self.after_connect = Some(sql.into());
+        self
+    }
+
+    /// decides whether the container is removed on drop, defaults to RemovalMode::Always.
+    pub fn removal_mode(mut self, removal_mode: RemovalMode) -> Self {
+        self.removal_mode = removal_mode;
+        self
+    }
+
+    /// generates the database, user, password and container names, and the
+    /// names of the forked databases, from seed instead of randomly, so a re-run
+    /// with the same seed gets the same names,
+    /// e.g. to trace a failing test's container. Testers running at the same
+    /// time need different seeds, or their names collide.
+    pub fn rng_seed(mut self, seed: u64) -> Self {
+        self.rng_seed = Some(seed);
+        self
+    }
+
+    /// starts the container with these options, e.g. with StartOptions::platform,
+    /// mounts or a network. The builder's timeout, reuse and server command win
+    /// when they're set, and StartOptions::timezone wins over the builder's.
+    pub fn start_options(mut self, start_options: StartOptions) -> Self {
+        self.start_options = start_options;
+        self
+    }
+
+    /// bounds the wait for the container and for Postgres to accept writes.
+    /// When unset, the `DOCKER_TESTER_TIMEOUT_SECS` env var is used, and 45 seconds without it.
+    pub fn timeout(mut self, timeout: time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// appends a query param to url and server_url, e.g. `application_name=test`.
+    /// The value is percent-encoded, so `options` can be `-c statement_timeout=5000`.
+    pub fn url_param(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.url_params.push((key.into(), value.into()));
+        self
+    }
+
+    /// sets the server's `max_connections` (`-c max_connections`), raise it above
+    /// the default 100 when many pools share the container.
+    pub fn max_connections(mut self, max_connections: u32) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    /// sets the timezone of the container (`TZ`), which initdb also makes the
+    /// server's `timezone`, e.g. `America/New_York`. Defaults to `UTC`, so the
+    /// tests don't depend on the image.
+    pub fn timezone(mut self, timezone: impl Into<String>) -> Self {
+        self.timezone = timezone.into();
+        self
+    }
+
+    /// appends args to the `postgres` server command, e.g.
+    /// `["-c", "shared_preload_libraries=pg_stat_statements"]`.
+    /// Preloaded libraries must be installed in the image.
+    pub fn pg_args(mut self, pg_args: &[&str]) -> Self {
+        self.pg_args
+            .extend(pg_args.iter().map(|arg| arg.to_string()));
+        self
+    }
+
+    /// runs the migrations in this schema, creating it first, so each migration
+    /// set has its own `_sqlx_migrations` table. Only the migrations use its
+    /// `search_path`, use after_connect to set it on the pools too.
+    pub fn migration_schema(mut self, schema: impl Into<String>) -> Self {
+        self.migration_schema = Some(schema.into());
+        self
+    }
+
+    /// starts Postgres with this `postgresql.conf`, bind-mounted read-only, for
+    /// the same settings as production. `listen_addresses` is still set to `*`
+    /// so the server can be reached. It needs a local docker daemon.
+    pub fn config_file(mut self, config_file: impl Into<PathBuf>) -> Self {
+        self.config_file = Some(config_file.into());
+        self
+    }
+
+    /// starts the Postgres container and creates the database.
+    pub async fn build(self) -> Result<TestPostgres, anyhow::Error> {
+        let started = time::Instant::now();
+        for (key, _) in &self.url_params {
+            if key.is_empty() || key.contains(['&', '=']) {
+                return Err(anyhow::anyhow!("invalid Postgres url param key {key:?}"));
+            }
+        }
+        // config databse
+        let mut names = NameSource::new(self.rng_seed);
+        let dbname = self
+            .dbname
+            .clone()
+            .unwrap_or_else(|| format!("test_postgres_{}", names.uuid()));
+        let image = self.image.as_str();
+        let port = "5432";
+        if self.reuse && self.owner.is_some() {
+            return Err(anyhow::anyhow!(
+                "a reused Postgres container can't have an owner, the role would already exist"
+            ));
+        }
+        // random credentials would never match a container to reuse
+        let user = self.user.clone().unwrap_or_else(|| match self.reuse {
+            true => "postgres_user".to_string(),
+            false => format!("postgres_user_{}", names.uuid()),
+        });
+        let charset = &self.password_charset;
+        if charset.is_empty() || !charset.chars().all(is_url_safe) {
+            return Err(anyhow::anyhow!(
+                "invalid password charset {charset:?}, only A-Z a-z 0-9 - . _ ~ are URL-safe"
+            ));
+        }
+        if self.password_length == 0 {
+            return Err(anyhow::anyhow!("the password length must not be 0"));
+        }
+        let password = self.password.clone().unwrap_or_else(|| match self.reuse {
+            true => "postgres_password".to_string(),
+            false => random_string(&mut names, charset, self.password_length),
+        });
+        let mut args = Vec::new();
+        if let Some(config_file) = &self.config_file {
+            // fail with the path instead of a server that doesn't boot
+            fs::File::open(config_file).map_err(|err| {
+                anyhow::anyhow!(
+                    "cannot read the Postgres config file {}: {err}",
+                    config_file.display()
+                )
+            })?;
+            let config_file = fs::canonicalize(config_file)?;
+            args.push("-v".to_string());
+            args.push(format!("{}:{CONFIG_FILE}:ro", config_file.display()));
+        }
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let timeout = self.timeout.or(self.start_options.timeout);
+        let mut opts = StartOptions {
+            timeout,
+            reuse: self.reuse,
+            timezone: self
+                .start_options
+                .timezone
+                .clone()
+                .or_else(|| Some(self.timezone.clone())),
+            ..self.start_options.clone()
+        };
+        opts.env.insert("POSTGRES_USER".to_string(), user.clone());
+        opts.env
+            .insert("POSTGRES_PASSWORD".to_string(), password.clone());
+        if self.rng_seed.is_some() && !self.reuse && opts.name.is_none() {
+            opts.name = Some(format!("docker-tester-postgres-{}", names.uuid().simple()));
+        }
+        let command = self.postgres_command();
+        if !command.is_empty() {
+            opts.command = command;
+        }
+        if self.reuse {
+            opts.labels
+                .insert(format!("{LABEL}.kind"), "postgres".to_string());
+        }
+        let start_options = opts.clone();
+        let container = start_container_with_opts_async(image, port, &args, opts)
+            .await
+            .map_err(DockerTesterError::from_start)?;
+        let mut test_postgres = TestPostgres {
+            dbname: dbname.clone(),
+            container_id: container.id.clone(),
+            host: container.host.clone(),
+            port: container.port,
+            user,
+            password,
+            owner: self.owner.clone(),
+            setup_duration: time::Duration::ZERO,
+            owner_password: random_string(&mut names, &self.password_charset, self.password_length),
+            after_connect: self.after_connect.clone(),
+            container,
+            replica: None,
+            start_options,
+            stop_opts: StopOptions {
+                remove_volumes: !self.keep_volumes,
+                ..Default::default()
+            },
+            removal_mode: self.removal_mode,
+            url_params: self.url_params.clone(),
+            owns_container: !self.reuse,
+            guarded: false,
+            migration_schema: self.migration_schema.clone(),
+            pool_size: self.pool_size,
+            acquire_timeout: self.acquire_timeout,
+            statement_timeout: self.statement_timeout,
+            pools: Mutex::default(),
+            names: Arc::new(Mutex::new(names)),
+        };
+        let exec_readiness = self.exec_readiness || remote_docker_host().is_some();
+        let mut backoff = Backoff::new(readiness_timeout(timeout)?);
+        loop {
+            match test_postgres.check_ready(exec_readiness).await {
+                Ok(()) => {
+                    info!("Postgres is ready to go");
+                    break;
+                }
+                Err(err) => match backoff.next_delay() {
+                    Some(delay) => {
+                        debug!("Postgres is not ready");
+                        tokio::time::sleep(delay).await;
+                    }
+                    None => return Err(err),
+                },
+            }
+        }
+        let mut conn = PgConnection::connect(&test_postgres.server_url())
+            .await
+            .map_err(connect_error)?;
+
+        for locale in [&self.lc_collate, &self.lc_ctype].into_iter().flatten() {
+            check_locale(&mut conn, locale).await?;
+        }
+        if let Some(owner) = &test_postgres.owner {
+            conn.execute(
+                format!(
+                    r#"CREATE ROLE "{}" LOGIN PASSWORD '{}';"#,
+                    owner, test_postgres.owner_password
+                )
+                .as_str(),
+            )
+            .await?;
+        }
+        // a database kept from an earlier run is migrated, and recreated when stale
+        let exists = self.recreate_on_mismatch && database_exists(&mut conn, &dbname).await?;
+        if exists {
+            info!("Postgres database {} exists, migrating it", dbname);
+        } else {
+            self.execute_create_database(&mut conn, &dbname)
+                .await
+                .map_err(DockerTesterError::CreateDatabase)?;
+            info!("Postgres created database {}", dbname.clone());
+        }
+        self.create_extensions(&test_postgres).await?;
+        // migrate database
+        let mut db_pool = self.migration_pool(&test_postgres.url()).await?;
+
+        match &self.migration_path {
+            Some(migration_path) => {
+                let m = Migrator::new(Path::new(migration_path))
+                    .await
+                    .map_err(migrate_error)?;
+                // applied migrations are skipped, so this only fails when the database is stale
+                if let Err(err) = m.run(&db_pool).await {
+                    match err {
+                        MigrateError::VersionMismatch(_) | MigrateError::VersionMissing(_)
+                            if self.recreate_on_mismatch =>
+                        {
+                            warn!("Postgres database {} is stale, recreating it", dbname);
+                            db_pool.close().await;
+                            conn.execute(
+                                format!(r#"DROP DATABASE "{}" WITH (FORCE);"#, dbname).as_str(),
+                            )
+                            .await
+                            .map_err(DockerTesterError::CreateDatabase)?;
+                            self.execute_create_database(&mut conn, &dbname)
+                                .await
+                                .map_err(DockerTesterError::CreateDatabase)?;
+                            self.create_extensions(&test_postgres).await?;
+                            db_pool = self.migration_pool(&test_postgres.url()).await?;
+                            m.run(&db_pool).await.map_err(migrate_error)?;
+                        }
+                        err => return Err(migrate_error(err)),
+                    }
+                }
+
+                info!("Postgres database {} migrated", dbname.clone());
+            }
+            None => info!("Postgres database {} created without migrations", dbname),
+        }
+        if !self.seeds.is_empty() {
+            self.run_seeds(&db_pool).await?;
+            info!("Postgres database {} seeded", dbname);
+        }
+        // a read-only PGDATA or a full disk only shows up on the first write
+        if let Err(err) = probe_writable(&db_pool).await {
+            let logs = container_logs(&test_postgres.container_id).unwrap_or_default();
+            return Err(anyhow::anyhow!(
+                "Postgres database {dbname} doesn't accept writes: {err}\n{logs}"
+            ));
+        }
+        if let Some(ready_when) = &self.ready_when {
+            let mut backoff = Backoff::new(readiness_timeout(timeout)?)
+                .interval(Some(time::Duration::from_millis(200)));
+            loop {
+                let not_ready = match ready_when(&db_pool).await {
+                    Ok(true) => break,
+                    Ok(false) => "ready_when returned false".to_string(),
+                    Err(err) => format!("ready_when failed: {err}"),
+                };
+                match backoff.next_delay() {
+                    Some(delay) => tokio::time::sleep(delay).await,
+                    None => {
+                        return Err(anyhow::anyhow!(
+                            "Postgres database {dbname} is not ready: {not_ready}"
+                        ))
+                    }
+                }
+            }
+        }
+        test_postgres.setup_duration = started.elapsed();
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            dbname = %dbname,
+            setup_duration = ?test_postgres.setup_duration,
+            "Postgres database ready"
+        );
+        db_pool.close().await;
+
+        Ok(test_postgres)
+    }
+
+    /// runs the seeds in a single transaction.
+    async fn run_seeds(&self, pool: &PgPool) -> Result<(), anyhow::Error> {
+        let mut tx = pool.begin().await?;
+        for seed in &self.seeds {
+            let sql = match seed {
+                Seed::Sql(sql) => sql.clone(),
+                Seed::File(path) => fs::read_to_string(path).map_err(|err| {
+                    anyhow::anyhow!("cannot read the seed file {}: {err}", path.display())
+                })?,
+            };
+            tx.execute(sql.as_str())
+                .await
+                .map_err(|err| anyhow::anyhow!("cannot seed the database: {err}"))?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// connects the pool running the migrations, in migration_schema when it's set.
+    async fn migration_pool(&self, url: &str) -> Result<PgPool, anyhow::Error> {
+        let Some(schema) = self.migration_schema.clone() else {
+            return PgPool::connect(url).await.map_err(connect_error);
+        };
+        let search_path = format!(r#"SET search_path TO "{}";"#, schema);
+        let pool = PgPoolOptions::default()
+            .after_connect(move |conn, _| {
+                let search_path = search_path.clone();
+                Box::pin(async move {
+                    conn.execute(search_path.as_str()).await?;
+                    Ok(())
+                })
+            })
+            .connect(url)
+            .await
+            .map_err(connect_error)?;
+        pool.execute(format!(r#"CREATE SCHEMA IF NOT EXISTS "{}";"#, schema).as_str())
+            .await?;
+        Ok(pool)
+    }
+
+    /// the server args passed as the container command, empty for the image's default.
+    fn postgres_command(&self) -> Vec<String> {
+        let mut settings = Vec::new();
+        if self.config_file.is_some() {
+            settings.push(format!("config_file={CONFIG_FILE}"));
+            settings.push("listen_addresses=*".to_string());
+        }
+        if let Some(max_connections) = self.max_connections {
+            settings.push(format!("max_connections={max_connections}"));
+        }
+        if settings.is_empty() && self.pg_args.is_empty() {
+            return Vec::new();
+        }
+        let mut command = vec!["postgres".to_string()];
+        for setting in settings {
+            command.push("-c".to_string());
+            command.push(setting);
+        }
+        command.extend(self.pg_args.iter().cloned());
+        command
+    }
+
+    /// runs CREATE DATABASE, retrying while the template is accessed by other users.
+    ///
+    /// CREATE DATABASE copies the template and fails when any other session is
+    /// connected to it, which happens when parallel testers share a fresh
+    /// container, so the failure is transient.
+    /// creates the extensions in the test database, before the migrations use them.
+    async fn create_extensions(&self, test_postgres: &TestPostgres) -> Result<(), anyhow::Error> {
+        if self.extensions.is_empty() {
+            return Ok(());
+        }
+        // the owner can't create most extensions, so create them as the superuser
+        let options = test_postgres
+            .server_url()
+            .parse::<PgConnectOptions>()?
+            .database(&test_postgres.dbname);
+        let mut db_conn = PgConnection::connect_with(&options)
+            .await
+            .map_err(connect_error)?;
+        for extension in &self.extensions {
+            db_conn
+                .execute(format!(r#"CREATE EXTENSION IF NOT EXISTS "{extension}";"#).as_str())
+                .await
+                .map_err(|err| anyhow::anyhow!("cannot create the extension {extension}: {err}"))?;
+        }
+        db_conn.close().await?;
+        Ok(())
+    }
+
+    async fn execute_create_database(
+        &self,
+        conn: &mut PgConnection,
+        dbname: &str,
+    ) -> Result<(), sqlx::Error> {
+        let sql = self.create_database_sql(dbname);
+        let mut retries = 0;
+        loop {
+            match conn.execute(sql.as_str()).await {
+                Err(sqlx::Error::Database(db_err))
+                    if retries < self.create_database_retries
+                        && is_template_contention(db_err.code().as_deref(), db_err.message()) =>
+                {
+                    retries += 1;
+                    debug!("Postgres template is busy, retrying CREATE DATABASE {dbname}");
+                    tokio::time::sleep(time::Duration::from_millis(100 * retries as u64)).await;
+                }
+                result => {
+                    result?;
+                    track_database(dbname);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn create_database_sql(&self, dbname: &str) -> String {
+        if let Some(create_database) = &self.create_database {
+            return create_database(dbname);
+        }
+        let mut sql = format!(r#"CREATE DATABASE "{}""#, dbname);
+        let template = match &self.template {
+            Some(template) => Some(template.as_str()),
+            None if self.lc_collate.is_some() || self.lc_ctype.is_some() => Some("template0"),
+            None => None,
+        };
+        if let Some(template) = template {
+            sql.push_str(&format!(r#" TEMPLATE "{}""#, template));
+        }
+        if let Some(encoding) = &self.encoding {
+            sql.push_str(&format!(" ENCODING '{}'", encoding.replace('\'', "''")));
+        }
+        if let Some(lc_collate) = &self.lc_collate {
+            sql.push_str(&format!(" LC_COLLATE '{}'", lc_collate.replace('\'', "''")));
+        }
+        if let Some(lc_ctype) = &self.lc_ctype {
+            sql.push_str(&format!(" LC_CTYPE '{}'", lc_ctype.replace('\'', "''")));
+        }
+        if let Some(owner) = &self.owner {
+            sql.push_str(&format!(r#" OWNER "{}""#, owner));
+        }
+        sql.push(';');
+        sql
+    }
+}
+
+/// turns the sqlx errors about changed migrations into an actionable message.
+fn migrate_error(err: MigrateError) -> anyhow::Error {
+    DockerTesterError::Migrate(err).into()
+}
+
+fn copy_csv_sql(table: &str, header: bool) -> String {
+    let table: Vec<String> = table
+        .split('.')
+        .map(|part| format!(r#""{}""#, part.replace('"', r#""""#)))
+        .collect();
+    format!(
+        "COPY {} FROM STDIN WITH (FORMAT csv, HEADER {});",
+        table.join("."),
+        header
+    )
+}
+
+/// writes to a temporary table in a rolled back transaction.
+async fn probe_writable(pool: &PgPool) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    tx.execute("CREATE TEMP TABLE _docker_tester_probe (id int);")
+        .await?;
+    tx.rollback().await
+}
+
+/// explains how to fix the "too many clients" error (SQLSTATE 53300) of a busy server.
+fn connect_error(err: sqlx::Error) -> anyhow::Error {
+    DockerTesterError::Connect(err).into()
+}
+
+/// DEFAULT_IMAGE is the Postgres image used unless TestPostgresBuilder::image is set.
+const DEFAULT_IMAGE: &str = "postgres:14-alpine";
+
+/// CONFIG_FILE is where TestPostgresBuilder::config_file is mounted in the container.
+const CONFIG_FILE: &str = "/etc/postgresql/postgresql.conf";
+
+const DEFAULT_POOL_SIZE: u32 = 5;
+
+/// resolves the pool size: explicit arg > `DOCKER_TESTER_PG_POOL_SIZE` > 5.
+fn pool_size(explicit: Option<u32>) -> u32 {
+    explicit
+        .or_else(|| {
+            std::env::var("DOCKER_TESTER_PG_POOL_SIZE")
+                .ok()
+                .and_then(|size| size.trim().parse::<u32>().ok())
+                .filter(|size| *size > 0)
+        })
+        .unwrap_or(DEFAULT_POOL_SIZE)
+}
+
+/// sets the statement timeout of the session in milliseconds, 0 would disable it.
+fn statement_timeout_sql(statement_timeout: time::Duration) -> String {
+    format!(
+        "SET statement_timeout = {}",
+        statement_timeout.as_millis().max(1)
+    )
+}
+
+/// tells whether c needs no percent-encoding in a url, the RFC 3986 unreserved characters.
+fn is_url_safe(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~')
+}
+
+/// NameSource generates the random names of a tester, reproducibly from a seed.
+struct NameSource {
+    /// the splitmix64 state, None for v4 uuids.
+    state: Option<u64>,
+}
+
+impl NameSource {
+    fn new(seed: Option<u64>) -> Self {
+        Self { state: seed }
+    }
+
+    /// gets a v4 uuid, random or the next one of the seed.
+    fn uuid(&mut self) -> Uuid {
+        let Some(state) = &mut self.state else {
+            return Uuid::new_v4();
+        };
+        let mut next = || {
+            // see https://prng.di.unimi.it/splitmix64.c
+            *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = *state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+        let bytes = ((next() as u128) << 64 | next() as u128).to_be_bytes();
+        uuid::Builder::from_random_bytes(bytes).into_uuid()
+    }
+}
+
+/// generates a random string of length characters of charset, from the random
+/// bytes of the uuids of names, rejecting the bytes that would bias the choice.
+fn random_string(names: &mut NameSource, charset: &str, length: usize) -> String {
+    let charset: Vec<char> = charset.chars().collect();
+    let limit = 256 - 256 % charset.len();
+    let mut random = Vec::with_capacity(length);
+    while random.len() < length {
+        let bytes = names.uuid().into_bytes();
+        // the bytes 6 and 8 carry the fixed version and variant bits
+        for (_, byte) in bytes.iter().enumerate().filter(|(i, _)| *i != 6 && *i != 8) {
+            if (*byte as usize) < limit && random.len() < length {
+                random.push(charset[*byte as usize % charset.len()]);
+            }
+        }
+    }
+    random.into_iter().collect()
+}
+
+/// tells whether CREATE DATABASE failed because another session used the template,
+/// SQLSTATE 55006 is object_in_use.
+fn is_template_contention(code: Option<&str>, message: &str) -> bool {
+    code == Some("55006") && message.contains("is being accessed by other users")
+}
+
+/// tells whether the server has a database named dbname.
+async fn database_exists(conn: &mut PgConnection, dbname: &str) -> Result<bool, anyhow::Error> {
+    let (exists,): (bool,) =
+        sqlx::query_as("SELECT EXISTS (SELECT 1 FROM pg_database WHERE datname = $1)")
+            .bind(dbname)
+            .fetch_one(conn)
+            .await?;
+    Ok(exists)
+}
+
+/// checks the locale is known to the server, so a typo fails with a clear message.
+async fn check_locale(conn: &mut PgConnection, locale: &str) -> Result<(), anyhow::Error> {
+    if locale == "C" || locale == "POSIX" {
+        return Ok(());
+    }
+    let (available,): (bool,) = sqlx::query_as(
+        "SELECT EXISTS (SELECT 1 FROM pg_collation WHERE collname = $1 OR collcollate = $1)",
+    )
+    .bind(locale)
+    .fetch_one(conn)
+    .await?;
+    if !available {
+        return Err(anyhow::anyhow!(
+            "the locale {locale} is not available in the Postgres image"
+        ));
+    }
+    Ok(())
+}
+
+impl TestPostgres {
+    /// creates a TestPostgres.
+    pub async fn new(migration_path: impl Into<String>) -> Result<Self, anyhow::Error> {
+        Self::builder().migration_path(migration_path).build().await
+    }
+
+    /// creates a TestPostgres with an empty database, without running migrations.
+    pub async fn new_without_migrations() -> Result<Self, anyhow::Error> {
+        Self::builder().without_migrations().build().await
+    }
+
+    /// creates a TestPostgres running the Postgres image, e.g. `postgres:16-alpine`.
+    pub async fn with_image(
+        image: impl Into<String>,
+        migration_path: impl Into<String>,
+    ) -> Result<Self, anyhow::Error> {
+        Self::builder()
+            .image(image)
+            .migration_path(migration_path)
+            .build()
+            .await
+    }
+
+    /// creates a TestPostgres running `pgvector/pgvector:pg16`, with the `vector`
+    /// extension created before the migrations, for embeddings tests.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::TestPostgres;
+    ///
+    /// # async fn run() {
+    /// let test_postgres = TestPostgres::with_pgvector("./migrations").await.unwrap();
+    /// assert!(test_postgres.extension_version("vector").await.unwrap().is_some());
+    /// # }
+    /// ```
+    pub async fn with_pgvector(migration_path: impl Into<String>) -> Result<Self, anyhow::Error> {
+        Self::builder()
+            .image(PGVECTOR_IMAGE)
+            .extension("vector")
+            .migration_path(migration_path)
+            .build()
+            .await
+    }
+
+    /// gets the installed version of the extension in the database, None
+    /// when it isn't installed, e.g. to assert the image ships the expected one.
+    pub async fn extension_version(
+        &self,
+        extension: &str,
+    ) -> Result<Option<String>, anyhow::Error> {
+        let mut conn = PgConnection::connect(&self.url())
+            .await
+            .map_err(connect_error)?;
+        let version: Option<(String,)> =
+            sqlx::query_as("SELECT extversion FROM pg_extension WHERE extname = $1")
+                .bind(extension)
+                .fetch_optional(&mut conn)
+                .await?;
+        conn.close().await?;
+        Ok(version.map(|(version,)| version))
+    }
+
+    /// creates a TestPostgres passing pg_args to the `postgres` server command,
+    /// preloaded libraries must be installed in the image.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::TestPostgres;
+    ///
+    /// # async fn run() {
+    /// let test_postgres = TestPostgres::with_pg_args(
+    ///     &["-c", "shared_preload_libraries=pg_stat_statements"],
+    ///     "./migrations",
+    /// )
+    /// .await
+    /// .unwrap();
+    /// # }
+    /// ```
+    pub async fn with_pg_args(
+        pg_args: &[&str],
+        migration_path: impl Into<String>,
+    ) -> Result<Self, anyhow::Error> {
+        Self::builder()
+            .pg_args(pg_args)
+            .migration_path(migration_path)
+            .build()
+            .await
+    }
+
+    /// creates a TestPostgres whose container starts with opts, e.g. to run it
+    /// on another platform or network, see TestPostgresBuilder::start_options.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::{StartOptions, TestPostgres};
+    ///
+    /// # async fn run() {
+    /// let opts = StartOptions {
+    ///     platform: Some("linux/amd64".to_string()),
+    ///     ..Default::default()
+    /// };
+    /// let test_postgres = TestPostgres::new_with_opts("./migrations", opts)
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub async fn new_with_opts(
+        migration_path: impl Into<String>,
+        opts: StartOptions,
+    ) -> Result<Self, anyhow::Error> {
+        Self::builder()
+            .start_options(opts)
+            .migration_path(migration_path)
+            .build()
+            .await
+    }
+
+    /// checks that no database this process created is left on the server, e.g.
+    /// in a last test when the testers share a reused container, to catch a
+    /// tester whose drop didn't clean up.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::TestPostgres;
+    ///
+    /// # async fn run() {
+    /// let test_postgres = TestPostgres::builder().reuse(true).build().await.unwrap();
+    /// let server_url = test_postgres.server_url();
+    /// drop(test_postgres);
+    /// TestPostgres::assert_clean(&server_url).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn assert_clean(server_url: &str) -> Result<(), anyhow::Error> {
+        let created = CREATED_DATABASES
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone();
+        let mut conn = PgConnection::connect(server_url)
+            .await
+            .map_err(connect_error)?;
+        let left: Vec<(String,)> = sqlx::query_as(
+            "SELECT datname FROM pg_database WHERE datname = ANY($1) ORDER BY datname",
+        )
+        .bind(&created)
+        .fetch_all(&mut conn)
+        .await?;
+        conn.close().await?;
+        if !left.is_empty() {
+            let left: Vec<String> = left.into_iter().map(|(dbname,)| dbname).collect();
+            return Err(anyhow::anyhow!(
+                "{} test databases are left on the server: {}",
+                left.len(),
+                left.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    /// creates a TestPostgresBuilder to customize the created database.
+    pub fn builder() -> TestPostgresBuilder {
+        TestPostgresBuilder::default()
+    }
+
+    /// gets a postgres db pool.
+    ///
+    /// The pool size is TestPostgresBuilder::pool_size, or read from the
+    /// `DOCKER_TESTER_PG_POOL_SIZE` env var, falling back to 5.
+    /// Use get_pool_with_size to set it explicitly.
+    pub async fn get_pool(&self) -> PgPool {
+        self.get_pool_with_size(pool_size(self.pool_size)).await
+    }
+
+    /// gets a postgres db pool with max_connections connections, which wins
+    /// over the `DOCKER_TESTER_PG_POOL_SIZE` env var.
+    pub async fn get_pool_with_size(&self, max_connections: u32) -> PgPool {
+        self.pool(max_connections, false).await
+    }
+
+    /// gets a postgres db pool whose transactions are read-only, so any write
+    /// fails, e.g. for read-path tests against a shared seeded database.
+    pub async fn readonly_pool(&self) -> PgPool {
+        self.pool(pool_size(self.pool_size), true).await
+    }
+
+    async fn pool(&self, max_connections: u32, read_only: bool) -> PgPool {
+        let mut statements = Vec::new();
+        if read_only {
+            statements.push("SET default_transaction_read_only = on".to_string());
+        }
+        if let Some(statement_timeout) = self.statement_timeout {
+            statements.push(statement_timeout_sql(statement_timeout));
+        }
+        statements.extend(self.after_connect.clone());
+        let mut options = PgPoolOptions::default().max_connections(max_connections);
+        if let Some(acquire_timeout) = self.acquire_timeout {
+            options = options.acquire_timeout(acquire_timeout);
+        }
+        if !statements.is_empty() {
+            options = options.after_connect(move |conn, _| {
+                let statements = statements.clone();
+                Box::pin(async move {
+                    for sql in statements {
+                        conn.execute(sql.as_str()).await?;
+                    }
+                    Ok(())
+                })
+            });
+        }
+        let pool = options
+            .connect(&self.url())
+            .await
+            .map_err(connect_error)
+            .unwrap();
+        let mut pools = self.pools.lock().unwrap_or_else(|err| err.into_inner());
+        pools.retain(|pool| !pool.is_closed());
+        pools.push(pool.clone());
+        pool
+    }
+
+    /// hands the container over to a ContainerGuard, leaving the pools open.
+    fn into_guard(mut self) -> ContainerGuard {
+        debug_assert!(self.owns_container && self.replica.is_none());
+        self.guarded = true;
+        self.pools
+            .get_mut()
+            .unwrap_or_else(|err| err.into_inner())
+            .clear();
+        ContainerGuard::new(self.container.clone())
+            .stop_opts(self.stop_opts.clone())
+            .removal_mode(self.removal_mode)
+    }
+
+    /// closes the pools handed out, so their connections don't fail with
+    /// "connection reset" once the container or the database is gone.
+    fn close_pools(&mut self) {
+        let pools = std::mem::take(self.pools.get_mut().unwrap_or_else(|err| err.into_inner()));
+        for pool in pools {
+            match tokio::runtime::Handle::try_current() {
+                // wait for the connections to be closed gracefully
+                Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                    tokio::task::block_in_place(|| handle.block_on(pool.close()))
+                }
+                // blocking a current-thread runtime would hang, only refuse new
+                // connections and let the idle ones go with the pool
+                _ => drop(pool.close()),
+            }
+        }
+    }
+
+    /// checks once whether Postgres accepts connections and writes.
+    ///
+    /// A starting server may accept connections while it's still in recovery,
+    /// where CREATE DATABASE fails with "the database system is starting up".
+    async fn check_ready(&self, exec_readiness: bool) -> Result<(), anyhow::Error> {
+        if exec_readiness {
+            // the entrypoint's init server only listens on the socket, so ask over tcp
+            let cmd = ["pg_isready", "-h", "127.0.0.1", "-U", &self.user];
+            if !exec_succeeds(&self.container_id, &cmd)? {
+                return Err(anyhow::anyhow!(
+                    "pg_isready failed in {}",
+                    self.container_id
+                ));
+            }
+            let password = format!("PGPASSWORD={}", self.password);
+            let cmd = [
+                "env",
+                &password,
+                "psql",
+                "-h",
+                "127.0.0.1",
+                "-U",
+                &self.user,
+                "-d",
+                "postgres",
+                "-tAc",
+                "SELECT pg_is_in_recovery()",
+            ];
+            let output = exec_output(&self.container_id, &cmd)?;
+            if String::from_utf8(output.stdout)?.trim() != "f" {
+                return Err(anyhow::anyhow!("Postgres is still in recovery"));
+            }
+            return Ok(());
+        }
+        let mut conn = PgConnection::connect(&self.server_url())
+            .await
+            .map_err(connect_error)?;
+        let (in_recovery,): (bool,) = sqlx::query_as("SELECT pg_is_in_recovery()")
+            .fetch_one(&mut conn)
+            .await?;
+        conn.close().await?;
+        if in_recovery {
+            return Err(anyhow::anyhow!("Postgres is still in recovery"));
+        }
+        Ok(())
+    }
+
+    /// bulk-loads a CSV file into the table of the test database with
+    /// `COPY ... FROM STDIN`, much faster than inserting large fixtures row by row.
+    /// Set header when the first line holds the column names. Returns the row count.
+    pub async fn load_csv(
+        &self,
+        table: &str,
+        csv_path: impl AsRef<Path>,
+        header: bool,
+    ) -> Result<u64, anyhow::Error> {
+        let csv_path = csv_path.as_ref();
+        let data = fs::read(csv_path).map_err(|err| {
+            anyhow::anyhow!("cannot read the CSV file {}: {err}", csv_path.display())
+        })?;
+        let mut conn = PgConnection::connect(&self.url()).await?;
+        let mut copy = conn.copy_in_raw(&copy_csv_sql(table, header)).await?;
+        // send the file in chunks rather than as a single message
+        for chunk in data.chunks(64 * 1024) {
+            if let Err(err) = copy.send(chunk).await {
+                copy.abort(err.to_string()).await?;
+                return Err(err.into());
+            }
+        }
+        let rows = copy.finish().await?;
+        conn.close().await?;
+        Ok(rows)
+    }
+
+    /// runs admin sql on the maintenance database of the server instead of the
+    /// test database, e.g. `CREATE ROLE` or `ALTER SYSTEM`.
+    pub async fn exec_server_sql(&self, sql: &str) -> Result<(), anyhow::Error> {
+        let mut conn = PgConnection::connect(&self.server_url())
+            .await
+            .map_err(connect_error)?;
+        conn.execute(sql).await?;
+        conn.close().await?;
+        Ok(())
+    }
+
+    /// creates a new database using this one as `TEMPLATE`, so tests can start
+    /// from a seeded state and diverge. The fork shares the container and only
+    /// drops its own database, so it must be dropped before this TestPostgres.
+    ///
+    /// Postgres can't copy a database with open connections, so the connections
+    /// to this database are terminated first.
+    pub async fn fork(&self) -> Result<TestPostgres, anyhow::Error> {
+        let started = time::Instant::now();
+        let dbname = format!(
+            "test_postgres_{}",
+            self.names
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .uuid()
+        );
+        let mut conn = PgConnection::connect(&self.server_url()).await?;
+        sqlx::query(
+            "SELECT pg_terminate_backend(pid) FROM pg_stat_activity WHERE datname = $1 AND pid <> pg_backend_pid()",
+        )
+        .bind(&self.dbname)
+        .execute(&mut conn)
+        .await?;
+        let mut sql = format!(r#"CREATE DATABASE "{}" TEMPLATE "{}""#, dbname, self.dbname);
+        if let Some(owner) = &self.owner {
+            sql.push_str(&format!(r#" OWNER "{}""#, owner));
+        }
+        conn.execute(format!("{};", sql).as_str()).await?;
+        track_database(&dbname);
+        conn.close().await?;
+        info!("Postgres forked database {} into {}", self.dbname, dbname);
+
+        Ok(TestPostgres {
+            host: self.host.clone(),
+            port: self.port,
+            user: self.user.clone(),
+            password: self.password.clone(),
+            dbname,
+            container_id: self.container_id.clone(),
+            owner: self.owner.clone(),
+            setup_duration: started.elapsed(),
+            owner_password: self.owner_password.clone(),
+            after_connect: self.after_connect.clone(),
+            container: self.container.clone(),
+            replica: None,
+            start_options: self.start_options.clone(),
+            stop_opts: self.stop_opts.clone(),
+            removal_mode: self.removal_mode,
+            url_params: self.url_params.clone(),
+            owns_container: false,
+            guarded: false,
+            migration_schema: self.migration_schema.clone(),
+            pool_size: self.pool_size,
+            acquire_timeout: self.acquire_timeout,
+            statement_timeout: self.statement_timeout,
+            pools: Mutex::default(),
+            names: self.names.clone(),
+        })
+    }
+
+    /// waits until at least count connections to the database show up in
+    /// pg_stat_activity, e.g. once the service under test opened its pool.
+    pub async fn wait_for_connections(
+        &self,
+        count: i64,
+        timeout: time::Duration,
+    ) -> Result<(), anyhow::Error> {
+        let mut conn = PgConnection::connect(&self.server_url()).await?;
+        let deadline = time::Instant::now() + timeout;
+        loop {
+            let (connections,): (i64,) =
+                sqlx::query_as("SELECT count(*) FROM pg_stat_activity WHERE datname = $1")
+                    .bind(&self.dbname)
+                    .fetch_one(&mut conn)
+                    .await?;
+            if connections >= count {
+                conn.close().await?;
+                return Ok(());
+            }
+            if time::Instant::now() >= deadline {
+                return Err(anyhow::anyhow!(
+                    "timed out waiting for {count} connections to {}, found {connections}",
+                    self.dbname
+                ));
+            }
+            tokio::time::sleep(time::Duration::from_millis(100)).await;
+        }
+    }
+
+    /// waits until the `_sqlx_migrations` table records a migration version of at
+    /// least version, when the migrations are applied by another process.
+    pub async fn wait_for_migration_version(
+        &self,
+        version: i64,
+        timeout: time::Duration,
+    ) -> Result<(), anyhow::Error> {
+        let mut conn = PgConnection::connect(&self.url()).await?;
+        let table = match &self.migration_schema {
+            Some(schema) => format!(r#""{}"._sqlx_migrations"#, schema),
+            None => "_sqlx_migrations".to_string(),
+        };
+        let deadline = time::Instant::now() + timeout;
+        loop {
+            let (exists,): (bool,) = sqlx::query_as("SELECT to_regclass($1) IS NOT NULL")
+                .bind(&table)
+                .fetch_one(&mut conn)
+                .await?;
+            let mut current: Option<i64> = None;
+            if exists {
+                (current,) =
+                    sqlx::query_as(&format!("SELECT max(version) FROM {table} WHERE success"))
+                        .fetch_one(&mut conn)
+                        .await?;
+            }
+            if current.is_some_and(|current| current >= version) {
+                conn.close().await?;
+                return Ok(());
+            }
+            if time::Instant::now() >= deadline {
+                return Err(anyhow::anyhow!(
+                    "timed out waiting for migration {version} in {}, found {current:?}",
+                    self.dbname
+                ));
+            }
+            tokio::time::sleep(time::Duration::from_millis(100)).await;
+        }
+    }
+
+    /// gets the Postgres container, e.g. to exec a command in it or get its logs.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::TestPostgres;
+    ///
+    /// # async fn run() {
+    /// let test_postgres = TestPostgres::new("./migrations").await.unwrap();
+    /// let output = test_postgres.container().exec(&["pg_isready"]).unwrap();
+    /// assert!(output.success());
+    /// # }
+    /// ```
+    pub fn container(&self) -> &Container {
+        &self.container
+    }
+
+    pub fn server_url(&self) -> String {
+        format!(
+            "postgres://{}@{}:{}{}",
+            url_credentials(&self.user, &self.password),
+            self.host,
+            self.port,
+            self.query_string()
+        )
+    }
+
+    pub fn url(&self) -> String {
+        self.url_at(&self.host, self.port)
+    }
+
+    /// builds the url of the test database on the given server.
+    fn url_at(&self, host: &str, port: u16) -> String {
+        let (user, password) = match &self.owner {
+            Some(owner) => (owner, &self.owner_password),
+            None => (&self.user, &self.password),
+        };
+        format!(
+            "postgres://{}@{}:{}/{}{}",
+            url_credentials(user, password),
+            host,
+            port,
+            self.dbname,
+            self.query_string()
+        )
+    }
+
+    /// builds the `?key=value&...` suffix of the urls, empty without url params.
+    fn query_string(&self) -> String {
+        query_string(&self.url_params)
+    }
+}
+
+fn query_string(params: &[(String, String)]) -> String {
+    if params.is_empty() {
+        return String::new();
+    }
+    let params: Vec<String> = params
+        .iter()
+        .map(|(key, value)| format!("{}={}", percent_encode(key), percent_encode(value)))
+        .collect();
+    format!("?{}", params.join("&"))
+}
+
+/// builds the `user:password` part of a url, percent-encoded so a password
+/// with `@`, `:` or `/` doesn't break it, without the `:` for an empty password.
+fn url_credentials(user: &str, password: &str) -> String {
+    if password.is_empty() {
+        percent_encode(user)
+    } else {
+        format!("{}:{}", percent_encode(user), percent_encode(password))
+    }
+}
+
+/// percent-encodes everything but the unreserved characters of RFC 3986.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Starts a postgres container with a migrated database like TestPostgres::new,
+/// and returns a pool to it with a guard stopping the container on drop.
+///
+/// It's a thinner alternative to carrying the TestPostgres around, e.g. for
+/// helpers returning the pool. Keep the guard alive as long as the pool is used.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::start_postgres;
+///
+/// # async fn run() {
+/// let (pool, _guard) = start_postgres("./migrations").await.unwrap();
+/// sqlx::query("SELECT 1").execute(&pool).await.unwrap();
+/// // the container is removed when _guard is dropped
+/// # }
+/// ```
+pub async fn start_postgres(
+    migration_path: impl Into<String>,
+) -> Result<(PgPool, ContainerGuard), anyhow::Error> {
+    let test_postgres = TestPostgres::new(migration_path).await?;
+    let pool = test_postgres.get_pool().await;
+    Ok((pool, test_postgres.into_guard()))
+}
+
+impl Drop for TestPostgres {
+    fn drop(&mut self) {
+        if self.guarded {
+            return;
+        }
+        self.close_pools();
+        if !self.removal_mode.should_remove() {
+            info!(
+                "Postgres container {} kept, connect with {}",
+                self.container_id,
+                self.url()
+            );
+            return;
+        }
+        // a panic while panicking aborts, so only report the failures
+        if let Some(replica) = &self.replica {
+            if let Err(err) =
+                stop_container_with_opts(replica.container_id.clone(), &self.stop_opts)
+            {
+                warn!(
+                    "Postgres replica container {} cannot be stopped: {err}",
+                    replica.container_id
+                );
+            }
+        }
+        if !self.owns_container {
+            // drop is sync, so drop the forked or reused database with psql in the container
+            let password = format!("PGPASSWORD={}", self.password);
+            let sql = format!(r#"DROP DATABASE "{}" WITH (FORCE);"#, self.dbname);
+            let cmd = [
+                "env",
+                &password,
+                "psql",
+                "-h",
+                "127.0.0.1",
+                "-U",
+                &self.user,
+                "-d",
+                "postgres",
+                "-c",
+                &sql,
+            ];
+            match exec_output(&self.container_id, &cmd) {
+                Ok(output) if output.status.success() => {
+                    info!("Postgres database {} dropped", self.dbname)
+                }
+                Ok(output) => warn!(
+                    "Postgres database {} cannot be dropped: {}",
+                    self.dbname,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                Err(err) => warn!("Postgres database {} cannot be dropped: {err}", self.dbname),
+            }
+            return;
+        }
+        match stop_container_with_opts(self.container_id.clone(), &self.stop_opts) {
+            Ok(()) => info!("Postgres container {} dropped", self.container_id),
+            Err(err) => warn!(
+                "Postgres container {} cannot be stopped: {err}",
+                self.container_id
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_db_should_create_and_drop() {
+        // create a postgres container on here
+        let test_postgres = TestPostgres::new("./migrations").await.unwrap();
+        let pool = test_postgres.get_pool().await;
+        // insert todo
+        sqlx::query("INSERT INTO todos (title) VALUES ('test')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // get todo
+        let (id, title) = sqlx::query_as::<_, (i32, String)>("SELECT id, title FROM todos")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(id, 1);
+        assert_eq!(title, "test");
+        // drop the postgres container on here
+    }
+
+    #[tokio::test]
+    async fn start_postgres_should_stop_the_container_with_the_guard() {
+        let (pool, guard) = start_postgres("./migrations").await.unwrap();
+        sqlx::query("INSERT INTO todos (title) VALUES ('test')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let id = guard.id.clone();
+        drop(guard);
+        assert!(!crate::list_containers()
+            .unwrap()
+            .iter()
+            .any(|container| id.starts_with(&container.id)));
+    }
+
+    #[tokio::test]
+    async fn test_db_should_fork_and_drop() {
+        let test_postgres = TestPostgres::new("./migrations").await.unwrap();
+        let pool = test_postgres.get_pool().await;
+        sqlx::query("INSERT INTO todos (title) VALUES ('seeded')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let fork = test_postgres.fork().await.unwrap();
+        let pool = fork.get_pool().await;
+        let (title,) = sqlx::query_as::<_, (String,)>("SELECT title FROM todos")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(title, "seeded");
+        pool.close().await;
+        drop(fork);
+    }
+
+    #[test]
+    fn migrate_error_should_explain_stale_database() {
+        let err = migrate_error(MigrateError::VersionMismatch(20221128135505));
+        assert_eq!(
+            err.to_string(),
+            "migration 20221128135505 changed since it was applied; the reused database is stale — recreate it"
+        );
+        assert!(matches!(
+            err.downcast_ref::<DockerTesterError>(),
+            Some(DockerTesterError::Migrate(_))
+        ));
+    }
+
+    #[test]
+    fn query_string_should_percent_encode_params() {
+        assert_eq!(query_string(&[]), "");
+        let params = [
+            ("application_name".to_string(), "test".to_string()),
+            (
+                "options".to_string(),
+                "-c statement_timeout=5000".to_string(),
+            ),
+        ];
+        assert_eq!(
+            query_string(&params),
+            "?application_name=test&options=-c%20statement_timeout%3D5000"
+        );
+    }
+
+    #[test]
+    fn url_credentials_should_percent_encode_the_user_and_password() {
+        assert_eq!(url_credentials("postgres", ""), "postgres");
+        assert_eq!(url_credentials("app", "p@ss:w/rd"), "app:p%40ss%3Aw%2Frd");
+        assert_eq!(url_credentials("the user", "secret"), "the%20user:secret");
+    }
+
+    #[test]
+    fn create_database_sql_should_use_template0_for_locale() {
+        let builder = TestPostgres::builder()
+            .encoding("UTF8")
+            .lc_collate("en_US.utf8")
+            .lc_ctype("en_US.utf8");
+        assert_eq!(
+            builder.create_database_sql("test"),
+            r#"CREATE DATABASE "test" TEMPLATE "template0" ENCODING 'UTF8' LC_COLLATE 'en_US.utf8' LC_CTYPE 'en_US.utf8';"#
+        );
+        assert_eq!(
+            TestPostgres::builder().create_database_sql("test"),
+            r#"CREATE DATABASE "test";"#
+        );
+        assert_eq!(
+            TestPostgres::builder()
+                .owner("app")
+                .create_database_sql("test"),
+            r#"CREATE DATABASE "test" OWNER "app";"#
+        );
+        assert_eq!(
+            TestPostgres::builder()
+                .owner("app")
+                .create_database(|dbname| format!(r#"CREATE DATABASE "{dbname}" TABLESPACE fast;"#))
+                .create_database_sql("test"),
+            r#"CREATE DATABASE "test" TABLESPACE fast;"#
+        );
+    }
+
+    #[test]
+    fn is_template_contention_should_match_the_busy_template() {
+        // parallel testers connecting to template1 of a fresh container made
+        // CREATE DATABASE fail intermittently
+        assert!(is_template_contention(
+            Some("55006"),
+            "source database \"template1\" is being accessed by other users"
+        ));
+        assert!(!is_template_contention(
+            Some("42P04"),
+            "database \"test\" already exists"
+        ));
+    }
+
+    #[test]
+    fn random_string_should_use_the_charset() {
+        let mut names = NameSource::new(None);
+        let password = random_string(&mut names, "ab", 100);
+        assert_eq!(password.len(), 100);
+        assert!(password.chars().all(|c| c == 'a' || c == 'b'));
+        let password = random_string(&mut names, ALPHANUMERIC, 32);
+        assert_eq!(password.len(), 32);
+        assert!(password.chars().all(is_url_safe));
+        assert_ne!(password, random_string(&mut names, ALPHANUMERIC, 32));
+        assert!(!is_url_safe('@'));
+    }
+
+    #[test]
+    fn name_source_should_repeat_a_seed() {
+        let (mut first, mut second) = (NameSource::new(Some(42)), NameSource::new(Some(42)));
+        let uuid = first.uuid();
+        assert_eq!(uuid, second.uuid());
+        assert_eq!(uuid.get_version_num(), 4);
+        assert_eq!(
+            random_string(&mut first, ALPHANUMERIC, 32),
+            random_string(&mut second, ALPHANUMERIC, 32)
+        );
+        assert_ne!(uuid, NameSource::new(Some(43)).uuid());
+    }
+
+    #[test]
+    fn statement_timeout_sql_should_use_milliseconds() {
+        assert_eq!(
+            statement_timeout_sql(time::Duration::from_secs(5)),
+            "SET statement_timeout = 5000"
+        );
+        assert_eq!(
+            statement_timeout_sql(time::Duration::from_micros(10)),
+            "SET statement_timeout = 1"
+        );
+    }
+
+    #[test]
+    fn copy_csv_sql_should_quote_the_table() {
+        assert_eq!(
+            copy_csv_sql("todos", true),
+            r#"COPY "todos" FROM STDIN WITH (FORMAT csv, HEADER true);"#
+        );
+        assert_eq!(
+            copy_csv_sql("app.todos", false),
+            r#"COPY "app"."todos" FROM STDIN WITH (FORMAT csv, HEADER false);"#
+        );
+    }
+
+    #[test]
+    fn postgres_command_should_pass_the_settings() {
+        assert!(TestPostgres::builder().postgres_command().is_empty());
+        assert_eq!(
+            TestPostgres::builder()
+                .max_connections(300)
+                .postgres_command(),
+            ["postgres", "-c", "max_connections=300"]
+        );
+        assert_eq!(
+            TestPostgres::builder()
+                .config_file("./postgresql.conf")
+                .postgres_command(),
+            [
+                "postgres",
+                "-c",
+                "config_file=/etc/postgresql/postgresql.conf",
+                "-c",
+                "listen_addresses=*"
+            ]
+        );
+        assert_eq!(
+            TestPostgres::builder()
+                .max_connections(300)
+                .pg_args(&["-c", "max_wal_size=2GB"])
+                .postgres_command(),
+            [
+                "postgres",
+                "-c",
+                "max_connections=300",
+                "-c",
+                "max_wal_size=2GB"
+            ]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use sqlx::{Connection, PgConnection};
+use std::time;
+
+use super::TestPostgres;
+use crate::error::docker_error;
+use crate::inspect::inspect;
+use crate::wait::{exec_output, readiness_timeout, Backoff};
+use crate::{start_container_with_opts_async, NetworkMode, StartOptions};
+
+const REPLICATION_SLOT: &str = "docker_tester_replica";
+
+/// NETWORKS_TEMPLATE lists the networks of a container as `name ip` lines.
+const NETWORKS_TEMPLATE: &str =
+    "{{range $name, $net := .NetworkSettings.Networks}}{{$name}} {{$net.IPAddress}}\n{{end}}";
+
+/// Replica is a streaming replica of a TestPostgres, running in its own container.
+pub(super) struct Replica {
+    pub(super) container_id: String,
+    pub(super) host: String,
+    pub(super) port: u16,
+}
+
+impl TestPostgres {
+    /// starts a second Postgres container streaming from this one, and waits
+    /// until it replayed everything written so far. Use replica_url to connect.
+    ///
+    /// The replica is cloned with `pg_basebackup` through a physical replication
+    /// slot, so it has the created database, the roles and the migrations.
+    pub async fn with_replica(mut self) -> Result<Self, anyhow::Error> {
+        let mut conn = PgConnection::connect(&self.server_url()).await?;
+        sqlx::query("SELECT pg_create_physical_replication_slot($1)")
+            .bind(REPLICATION_SLOT)
+            .execute(&mut conn)
+            .await?;
+        // the image only allows regular connections from other hosts
+        let output = exec_output(
+            &self.container_id,
+            &[
+                "sh",
+                "-c",
+                r#"echo "host replication all all md5" >> "$PGDATA/pg_hba.conf""#,
+            ],
+        )?;
+        if !output.status.success() {
+            return Err(docker_error(output.stderr));
+        }
+        sqlx::query("SELECT pg_reload_conf()")
+            .execute(&mut conn)
+            .await?;
+
+        let networks = inspect(&self.container_id, NETWORKS_TEMPLATE)?;
+        let (network_mode, primary_ip) = replication_network(&networks).ok_or_else(|| {
+            anyhow::anyhow!("the Postgres primary has no network address a replica can reach")
+        })?;
+        let script = format!(
+            "until pg_basebackup -h {primary_ip} -p 5432 -U {} -D /tmp/replica -S {REPLICATION_SLOT} -X stream -R; \
+             do sleep 1; done; chmod 700 /tmp/replica; exec postgres -D /tmp/replica",
+            self.user
+        );
+        let password = format!("PGPASSWORD={}", self.password);
+        // start like the primary, e.g. with its platform and env, but with its
+        // own name and volume, and never reused
+        let opts = StartOptions {
+            command: vec!["sh".to_string(), "-c".to_string(), script],
+            network_mode,
+            name: None,
+            named_volume: None,
+            reuse: false,
+            ..self.start_options.clone()
+        };
+        let container = start_container_with_opts_async(
+            &self.container.image,
+            "5432",
+            &["--user", "postgres", "-e", &password],
+            opts,
+        )
+        .await?;
+        self.replica = Some(Replica {
+            container_id: container.id,
+            host: container.host,
+            port: container.port,
+        });
+
+        let replica_url = self.replica_url().unwrap_or_default();
+        let (primary_lsn,): (String,) = sqlx::query_as("SELECT pg_current_wal_lsn()::text")
+            .fetch_one(&mut conn)
+            .await?;
+        let mut backoff = Backoff::new(readiness_timeout(self.start_options.timeout)?);
+        loop {
+            let err = match replayed(&replica_url, &primary_lsn).await {
+                Ok(true) => {
+                    info!("Postgres replica is ready to go");
+                    break;
+                }
+                Ok(false) => anyhow::anyhow!("Postgres replica did not catch up"),
+                Err(err) => err,
+            };
+            match backoff.next_delay() {
+                Some(delay) => {
+                    debug!("Postgres replica is not ready");
+                    tokio::time::sleep(delay).await;
+                }
+                None => return Err(err),
+            }
+        }
+        conn.close().await?;
+        Ok(self)
+    }
+
+    /// waits until the replica started by with_replica replayed everything the
+    /// primary wrote, or lags behind by at most max_lag, so a read from the
+    /// replica doesn't see stale data. The timeout error has the observed lag.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::TestPostgres;
+    /// use std::time::Duration;
+    ///
+    /// # async fn run() {
+    /// let test_postgres = TestPostgres::new("./migrations")
+    ///     .await
+    ///     .unwrap()
+    ///     .with_replica()
+    ///     .await
+    ///     .unwrap();
+    /// // write to the primary
+    /// test_postgres
+    ///     .wait_for_replica_lag(Duration::from_millis(100), Duration::from_secs(10))
+    ///     .await
+    ///     .unwrap();
+    /// // read from the replica
+    /// # }
+    /// ```
+    pub async fn wait_for_replica_lag(
+        &self,
+        max_lag: time::Duration,
+        timeout: time::Duration,
+    ) -> Result<(), anyhow::Error> {
+        if self.replica.is_none() {
+            return Err(anyhow::anyhow!(
+                "there is no replica, call with_replica first"
+            ));
+        }
+        let mut conn = PgConnection::connect(&self.server_url()).await?;
+        let deadline = time::Instant::now() + timeout;
+        loop {
+            // replay_lag is only known while there's WAL to replay
+            let replication: Option<(bool, Option<f64>)> = sqlx::query_as(
+                "SELECT replay_lsn >= pg_current_wal_lsn(), extract(epoch FROM replay_lag)::float8 \
+                 FROM pg_stat_replication LIMIT 1",
+            )
+            .fetch_optional(&mut conn)
+            .await?;
+            let lag = match replication {
+                Some((true, _)) => Some(time::Duration::ZERO),
+                Some((false, lag)) => lag.map(time::Duration::from_secs_f64),
+                None => None,
+            };
+            if lag.is_some_and(|lag| lag <= max_lag) {
+                conn.close().await?;
+                return Ok(());
+            }
+            if time::Instant::now() >= deadline {
+                let observed = match (replication, lag) {
+                    (None, _) => "the replica is not streaming".to_string(),
+                    (_, Some(lag)) => format!("the replica lags {lag:?} behind"),
+                    (_, None) => "the replica lag is unknown".to_string(),
+                };
+                return Err(anyhow::anyhow!(
+                    "timed out waiting for the replica lag to be at most {max_lag:?}, {observed}"
+                ));
+            }
+            tokio::time::sleep(time::Duration::from_millis(100)).await;
+        }
+    }
+
+    /// gets the url of the test database on the replica started by with_replica.
+    pub fn replica_url(&self) -> Option<String> {
+        let replica = self.replica.as_ref()?;
+        Some(self.url_at(&replica.host, replica.port))
+    }
+}
+
+/// picks the network the replica joins from the `name ip` lines of
+/// NETWORKS_TEMPLATE, and the primary's ip on it. A user-defined network,
+/// e.g. from StartOptions::network_mode, wins over the default bridge.
+fn replication_network(networks: &str) -> Option<(NetworkMode, String)> {
+    let mut reachable: Vec<(&str, &str)> = networks
+        .lines()
+        .filter_map(|line| line.trim().split_once(' '))
+        .filter(|(_, ip)| !ip.trim().is_empty())
+        .collect();
+    reachable.sort_by_key(|(name, _)| *name == "bridge");
+    let (name, ip) = reachable.first()?;
+    let network_mode = match *name {
+        "bridge" => NetworkMode::Bridge,
+        name => NetworkMode::Custom(name.to_string()),
+    };
+    Some((network_mode, ip.trim().to_string()))
+}
+
+/// tells whether the replica is in recovery and replayed the primary up to lsn.
+async fn replayed(replica_url: &str, lsn: &str) -> Result<bool, anyhow::Error> {
+    let mut conn = PgConnection::connect(replica_url).await?;
+    let (replayed,): (Option<bool>,) =
+        sqlx::query_as("SELECT pg_is_in_recovery() AND pg_last_wal_replay_lsn() >= $1::pg_lsn")
+            .bind(lsn)
+            .fetch_one(&mut conn)
+            .await?;
+    conn.close().await?;
+    Ok(replayed.unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replication_network_should_prefer_a_user_defined_network() {
+        assert_eq!(
+            replication_network("bridge 172.17.0.2\n"),
+            Some((NetworkMode::Bridge, "172.17.0.2".to_string()))
+        );
+        assert_eq!(
+            replication_network("bridge 172.17.0.2\norders 172.20.0.3\n"),
+            Some((
+                NetworkMode::Custom("orders".to_string()),
+                "172.20.0.3".to_string()
+            ))
+        );
+        // a network without an address, e.g. host, can't be dialed
+        assert_eq!(replication_network("host \n"), None);
+        assert_eq!(replication_network(""), None);
+    }
+}
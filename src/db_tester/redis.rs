@@ -0,0 +1,151 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::{thread, time};
+
+use crate::{
+    start_container_with_probe, stop_container, DockerTesterError, RemovalMode, StartOptions,
+};
+
+/// TestRedis contains a Redis connection infomation.
+pub struct TestRedis {
+    pub host: String,
+    pub port: u16,
+    pub container_id: String,
+    removal_mode: RemovalMode,
+}
+
+impl TestRedis {
+    /// creates a TestRedis, waiting until Redis answers `PING`.
+    pub fn new() -> Result<Self, anyhow::Error> {
+        // docker reports the container as running before Redis accepts connections
+        let container = start_container_with_probe(
+            "redis:7-alpine",
+            "6379",
+            &[],
+            StartOptions::default(),
+            |container| {
+                send_command(&container.host, container.port, &["PING"])
+                    .is_ok_and(|reply| reply == "+PONG")
+            },
+        )
+        .map_err(DockerTesterError::from_start)?;
+        info!("Redis is ready to go");
+        Ok(Self {
+            container_id: container.id,
+            host: container.host,
+            port: container.port,
+            removal_mode: RemovalMode::default(),
+        })
+    }
+
+    /// decides whether the container is removed on drop, defaults to RemovalMode::Always.
+    pub fn removal_mode(mut self, removal_mode: RemovalMode) -> Self {
+        self.removal_mode = removal_mode;
+        self
+    }
+
+    /// gets the `redis://host:port` url, e.g. for `redis::Client::open`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::TestRedis;
+    ///
+    /// let test_redis = TestRedis::new().unwrap();
+    /// let url = test_redis.connection_url();
+    /// ```
+    pub fn connection_url(&self) -> String {
+        format!("redis://{}:{}", self.host, self.port)
+    }
+
+    /// waits until the key exists, e.g. once the service under test populated the cache.
+    ///
+    /// It blocks like the rest of TestRedis, call it from
+    /// `tokio::task::spawn_blocking` in an async test.
+    pub fn wait_for_key(&self, key: &str, timeout: time::Duration) -> Result<(), anyhow::Error> {
+        let deadline = time::Instant::now() + timeout;
+        loop {
+            if self.command(&["EXISTS", key])? == ":1" {
+                return Ok(());
+            }
+            if time::Instant::now() >= deadline {
+                return Err(anyhow::anyhow!("timed out waiting for the Redis key {key}"));
+            }
+            thread::sleep(time::Duration::from_millis(100));
+        }
+    }
+
+    /// sends a command and returns the first line of the reply, e.g. `+PONG` or `:1`.
+    fn command(&self, args: &[&str]) -> Result<String, anyhow::Error> {
+        send_command(&self.host, self.port, args)
+    }
+}
+
+/// sends a command over a new connection and returns the first line of the reply.
+fn send_command(host: &str, port: u16, args: &[&str]) -> Result<String, anyhow::Error> {
+    let mut stream = TcpStream::connect((host, port))?;
+    stream.set_read_timeout(Some(time::Duration::from_secs(5)))?;
+    stream.write_all(encode_command(args).as_bytes())?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    if reply.is_empty() {
+        return Err(anyhow::anyhow!("Redis closed the connection"));
+    }
+    Ok(reply.trim_end().to_string())
+}
+
+impl Drop for TestRedis {
+    fn drop(&mut self) {
+        if !self.removal_mode.should_remove() {
+            info!(
+                "Redis container {} kept, connect with {}",
+                self.container_id,
+                self.connection_url()
+            );
+            return;
+        }
+        // a panic while panicking aborts, so only report the failure
+        match stop_container(self.container_id.clone()) {
+            Ok(()) => info!("Redis container {} dropped", self.container_id),
+            Err(err) => warn!(
+                "Redis container {} cannot be stopped: {err}",
+                self.container_id
+            ),
+        }
+    }
+}
+
+/// encodes the command as a RESP array of bulk strings.
+fn encode_command(args: &[&str]) -> String {
+    let mut command = format!("*{}\r\n", args.len());
+    for arg in args {
+        command.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+    }
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_command_should_use_bulk_strings() {
+        assert_eq!(
+            encode_command(&["EXISTS", "user:1"]),
+            "*2\r\n$6\r\nEXISTS\r\n$6\r\nuser:1\r\n"
+        );
+    }
+
+    #[test]
+    fn test_redis_should_wait_for_key() {
+        let test_redis = TestRedis::new().unwrap();
+        assert_eq!(
+            test_redis.connection_url(),
+            format!("redis://{}:{}", test_redis.host, test_redis.port)
+        );
+        let timeout = time::Duration::from_millis(300);
+        assert!(test_redis.wait_for_key("missing", timeout).is_err());
+        assert_eq!(test_redis.command(&["SET", "present", "1"]).unwrap(), "+OK");
+        test_redis.wait_for_key("present", timeout).unwrap();
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::{docker_logs, Container};
+
+/// LOG_DIR_ENV names the directory the logs of the containers removed while a test panics go to.
+const LOG_DIR_ENV: &str = "DOCKER_TESTER_LOG_DIR";
+
+/// STARTED tracks the containers started by this process and not removed yet, by id and image.
+static STARTED: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// tracks the started container for dump_all_logs.
+pub(crate) fn track(container: &Container) {
+    let mut started = STARTED.lock().unwrap_or_else(|err| err.into_inner());
+    // a reused container is started once per test
+    if !started.iter().any(|(id, _)| *id == container.id) {
+        started.push((container.id.clone(), container.image.clone()));
+    }
+}
+
+/// tells whether this process started the container and didn't remove it yet.
+pub(crate) fn is_tracked(id: &str) -> bool {
+    STARTED
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .iter()
+        .any(|(started, _)| id.starts_with(started.as_str()) || started.starts_with(id))
+}
+
+/// forgets the removed container, docker has no logs for it anymore.
+pub(crate) fn untrack(id: &str) {
+    STARTED
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .retain(|(started, _)| started != id);
+}
+
+/// writes the logs of the container about to be removed while the test panics,
+/// when DOCKER_TESTER_LOG_DIR is set.
+pub(crate) fn dump_on_panic(id: &str) {
+    if !std::thread::panicking() {
+        return;
+    }
+    let Ok(dir) = std::env::var(LOG_DIR_ENV) else {
+        return;
+    };
+    let image = STARTED
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .iter()
+        .find(|(started, _)| started == id)
+        .map(|(_, image)| image.clone())
+        .unwrap_or_default();
+    // the test already failed, a missing log file must not hide why
+    if let Err(err) = dump(Path::new(&dir), id, &image) {
+        warn!("Container[{id}] logs not written to {dir}: {err}");
+    }
+}
+
+/// Writes the full logs of every container this process started and didn't
+/// remove yet to a file per container in dir, named by image and id, e.g. in
+/// the teardown of a suite to keep a log bundle of a failed CI run.
+/// Set `DOCKER_TESTER_LOG_DIR` to also write them when a test panics.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::{dump_all_logs, start_container};
+///
+/// let container = start_container("redis:7-alpine", "6379", &[]).unwrap();
+/// // do something with the container
+/// dump_all_logs("target/container-logs").expect("Failed to dump the logs");
+/// ```
+pub fn dump_all_logs(dir: impl AsRef<Path>) -> Result<(), anyhow::Error> {
+    let dir = dir.as_ref();
+    let started = STARTED
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone();
+    let failures: Vec<String> = started
+        .iter()
+        .filter_map(|(id, image)| {
+            dump(dir, id, image)
+                .err()
+                .map(|err| format!("{id}: {}", err.to_string().trim()))
+        })
+        .collect();
+    if !failures.is_empty() {
+        return Err(anyhow::anyhow!(
+            "cannot dump the logs of {} containers: {}",
+            failures.len(),
+            failures.join("; ")
+        ));
+    }
+    Ok(())
+}
+
+fn dump(dir: &Path, id: &str, image: &str) -> Result<(), anyhow::Error> {
+    let logs = docker_logs(id, &[])?;
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(log_file_name(image, id)), logs)?;
+    Ok(())
+}
+
+/// names the log file of the container, e.g. `postgres_14-dfd60e4ef0c0.log`.
+fn log_file_name(image: &str, id: &str) -> String {
+    let image: String = image
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' => c,
+            _ => '_',
+        })
+        .collect();
+    let id = id.get(..12).unwrap_or(id);
+    match image.as_str() {
+        "" => format!("{id}.log"),
+        image => format!("{image}-{id}.log"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_file_name_should_be_a_safe_file_name() {
+        assert_eq!(
+            log_file_name(
+                "ghcr.io/org/postgres:14",
+                "dfd60e4ef0c0a1b2c3d4e5f6a7b8c9d0"
+            ),
+            "ghcr.io_org_postgres_14-dfd60e4ef0c0.log"
+        );
+        assert_eq!(log_file_name("", "dfd60e4e"), "dfd60e4e.log");
+    }
+}
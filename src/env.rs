@@ -0,0 +1,198 @@
+use crate::{stop_container_with_opts, Container, RemovalMode, StopOptions};
+
+/// TestEnv owns a set of named containers and stops them together, dependents
+/// before their dependencies, so an app doesn't log errors about a database
+/// that went away during its own shutdown.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::{start_container, TestEnv};
+///
+/// let mut env = TestEnv::new();
+/// env.add("db", start_container("postgres:14-alpine", "5432", &["-e", "POSTGRES_PASSWORD=password"]).unwrap());
+/// env.add("app", start_container("my-app:latest", "8080", &[]).unwrap());
+/// env.depends_on("app", "db");
+/// // stops app, then db
+/// env.teardown().unwrap();
+/// ```
+#[derive(Default)]
+pub struct TestEnv {
+    containers: Vec<(String, Container)>,
+    dependencies: Vec<(String, String)>,
+    stop_opts: StopOptions,
+    removal_mode: RemovalMode,
+}
+
+impl TestEnv {
+    /// creates an empty TestEnv.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// adds the container under name, it's stopped on teardown.
+    pub fn add(&mut self, name: impl Into<String>, container: Container) -> &Container {
+        self.containers.push((name.into(), container));
+        &self.containers[self.containers.len() - 1].1
+    }
+
+    /// gets the container added under name.
+    pub fn get(&self, name: &str) -> Option<&Container> {
+        self.containers
+            .iter()
+            .find(|(added, _)| added == name)
+            .map(|(_, container)| container)
+    }
+
+    /// declares that dependent uses dependency, so dependent is stopped first.
+    pub fn depends_on(&mut self, dependent: impl Into<String>, dependency: impl Into<String>) {
+        self.dependencies
+            .push((dependent.into(), dependency.into()));
+    }
+
+    /// decides whether drop stops the containers, defaults to RemovalMode::Always.
+    /// An explicit teardown always stops them.
+    pub fn removal_mode(&mut self, removal_mode: RemovalMode) {
+        self.removal_mode = removal_mode;
+    }
+
+    /// stops and removes the containers in dependency order, dependents first.
+    /// Containers without dependencies between them stop in reverse order of adding.
+    /// When depends_on names an unknown container or makes a cycle, every container
+    /// is still stopped, in reverse order of adding, and that error is returned.
+    pub fn teardown(&mut self) -> Result<(), anyhow::Error> {
+        let names: Vec<&str> = self
+            .containers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        let (order, mut result) = teardown_order(&names, &self.dependencies);
+        let mut containers: Vec<Option<(String, Container)>> =
+            self.containers.drain(..).map(Some).collect();
+        // keep stopping the others when one fails, and report the first failure
+        for index in order {
+            if let Some((name, container)) = containers[index].take() {
+                match stop_container_with_opts(container.id.clone(), &self.stop_opts) {
+                    Ok(()) => info!("TestEnv container {} ({}) stopped", name, container.id),
+                    Err(err) if result.is_ok() => result = Err(err),
+                    Err(_) => {}
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Drop for TestEnv {
+    fn drop(&mut self) {
+        if !self.removal_mode.should_remove() {
+            for (name, container) in self.containers.drain(..) {
+                info!(
+                    "TestEnv container {} ({}) kept, listening on {}:{}",
+                    name, container.id, container.host, container.port
+                );
+            }
+            return;
+        }
+        if let Err(err) = self.teardown() {
+            warn!("TestEnv teardown failed: {err}");
+        }
+    }
+}
+
+/// gets the order teardown stops names in, falling back to the reverse order of
+/// adding with the error when the dependencies can't be ordered, so nothing leaks.
+fn teardown_order(
+    names: &[&str],
+    dependencies: &[(String, String)],
+) -> (Vec<usize>, Result<(), anyhow::Error>) {
+    match stop_order(names, dependencies) {
+        Ok(order) => (order, Ok(())),
+        Err(err) => ((0..names.len()).rev().collect(), Err(err)),
+    }
+}
+
+/// sorts the indexes of names so each one comes after everything depending on it.
+fn stop_order(
+    names: &[&str],
+    dependencies: &[(String, String)],
+) -> Result<Vec<usize>, anyhow::Error> {
+    let index = |name: &str| {
+        names
+            .iter()
+            .position(|added| *added == name)
+            .ok_or_else(|| anyhow::anyhow!("TestEnv has no container named {name}"))
+    };
+    let mut edges = Vec::new();
+    for (dependent, dependency) in dependencies {
+        edges.push((index(dependent)?, index(dependency)?));
+    }
+    let mut order = Vec::new();
+    let mut stopped = vec![false; names.len()];
+    while order.len() < names.len() {
+        // a container can stop once all its dependents stopped
+        let next = (0..names.len()).rev().find(|&candidate| {
+            !stopped[candidate]
+                && edges
+                    .iter()
+                    .all(|&(dependent, dependency)| dependency != candidate || stopped[dependent])
+        });
+        match next {
+            Some(next) => {
+                stopped[next] = true;
+                order.push(next);
+            }
+            None => return Err(anyhow::anyhow!("TestEnv dependencies have a cycle")),
+        }
+    }
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deps(deps: &[(&str, &str)]) -> Vec<(String, String)> {
+        deps.iter()
+            .map(|(dependent, dependency)| (dependent.to_string(), dependency.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn stop_order_should_stop_dependents_first() {
+        let names = ["db", "cache", "app", "worker"];
+        let order = stop_order(
+            &names,
+            &deps(&[("app", "db"), ("app", "cache"), ("worker", "app")]),
+        )
+        .unwrap();
+        assert_eq!(order, [3, 2, 1, 0]);
+
+        let names = ["app", "db"];
+        assert_eq!(stop_order(&names, &deps(&[("app", "db")])).unwrap(), [0, 1]);
+        assert_eq!(stop_order(&names, &[]).unwrap(), [1, 0]);
+    }
+
+    #[test]
+    fn stop_order_should_reject_cycles_and_unknown_names() {
+        let names = ["app", "db"];
+        let err = stop_order(&names, &deps(&[("app", "db"), ("db", "app")])).unwrap_err();
+        assert_eq!(err.to_string(), "TestEnv dependencies have a cycle");
+        let err = stop_order(&names, &deps(&[("app", "queue")])).unwrap_err();
+        assert_eq!(err.to_string(), "TestEnv has no container named queue");
+    }
+
+    #[test]
+    fn teardown_order_should_still_stop_everything_on_a_bad_dependency() {
+        let names = ["db", "app", "worker"];
+        let (order, result) = teardown_order(&names, &deps(&[("app", "queue")]));
+        assert_eq!(order, [2, 1, 0]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "TestEnv has no container named queue"
+        );
+        let (order, result) = teardown_order(&names, &deps(&[("worker", "db")]));
+        assert_eq!(order, [2, 1, 0]);
+        assert!(result.is_ok());
+    }
+}
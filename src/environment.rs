@@ -0,0 +1,114 @@
+use std::sync::OnceLock;
+
+use crate::runtime::docker_command;
+
+/// DockerEnvironment tells how the docker daemon runs, which changes how
+/// the mapped ports and the host are reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DockerEnvironment {
+    /// Docker Desktop on macOS or Windows, the daemon runs in a VM and the
+    /// ports are forwarded to the loopback interface. `host.docker.internal`
+    /// is built in.
+    Desktop,
+    /// a daemon running on the Linux host itself, `host.docker.internal`
+    /// needs the `host-gateway` alias.
+    Native,
+}
+
+/// Detects the docker environment from the `OperatingSystem` of `docker info`,
+/// once per process. None when the daemon can't be asked, e.g. it isn't running.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::{docker_environment, DockerEnvironment};
+///
+/// if docker_environment() == Some(DockerEnvironment::Desktop) {
+///     println!("running on Docker Desktop");
+/// }
+/// ```
+pub fn docker_environment() -> Option<DockerEnvironment> {
+    static ENVIRONMENT: OnceLock<Option<DockerEnvironment>> = OnceLock::new();
+    *ENVIRONMENT.get_or_init(|| {
+        let output = docker_command()
+            .arg("info")
+            .arg("-f")
+            .arg("{{.OperatingSystem}}")
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        Some(parse_operating_system(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    })
+}
+
+fn parse_operating_system(os: &str) -> DockerEnvironment {
+    if os.trim().starts_with("Docker Desktop") {
+        DockerEnvironment::Desktop
+    } else {
+        DockerEnvironment::Native
+    }
+}
+
+/// HOST_ENV names the host the mapped ports are dialed on instead of the one docker reports.
+const HOST_ENV: &str = "DOCKER_TESTER_HOST";
+
+/// gets the host set with DOCKER_TESTER_HOST, e.g. the address of a remote daemon.
+pub(crate) fn host_override() -> Option<String> {
+    std::env::var(HOST_ENV)
+        .ok()
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+}
+
+/// gets the host the mapped ports are dialed on instead of the one docker
+/// reports: DOCKER_TESTER_HOST, or else the host of a remote DOCKER_HOST
+/// daemon, whose published ports live on that host rather than on loopback.
+pub(crate) fn dial_host_override() -> Option<String> {
+    host_override().or_else(crate::remote_docker_host)
+}
+
+/// turns the wildcard address docker reports for a mapped port into one
+/// that can be dialed, Docker Desktop and rootless setups don't route
+/// `0.0.0.0`. The override host wins over the reported one.
+pub(crate) fn dialable_host_ip(host_ip: String, override_host: Option<String>) -> String {
+    if let Some(host) = override_host {
+        return host;
+    }
+    match host_ip.as_str() {
+        "0.0.0.0" | "::" | "" => "127.0.0.1".to_string(),
+        _ => host_ip,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_operating_system_should_detect_docker_desktop() {
+        assert_eq!(
+            parse_operating_system("Docker Desktop\n"),
+            DockerEnvironment::Desktop
+        );
+        assert_eq!(
+            parse_operating_system("Ubuntu 22.04.1 LTS"),
+            DockerEnvironment::Native
+        );
+    }
+
+    #[test]
+    fn dialable_host_ip_should_replace_the_wildcard_address() {
+        for host_ip in ["0.0.0.0", "::", ""] {
+            assert_eq!(dialable_host_ip(host_ip.to_string(), None), "127.0.0.1");
+        }
+        assert_eq!(dialable_host_ip("10.0.0.5".to_string(), None), "10.0.0.5");
+        assert_eq!(
+            dialable_host_ip("0.0.0.0".to_string(), Some("docker.ci".to_string())),
+            "docker.ci"
+        );
+    }
+}
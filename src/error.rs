@@ -0,0 +1,138 @@
+use std::fmt;
+
+use sqlx::migrate::MigrateError;
+
+use crate::ReadinessSample;
+
+/// DockerTesterError tells which step of starting a container or creating a
+/// tester failed. start_container returns it, the other functions still return
+/// anyhow::Error, get the kind back with `err.downcast_ref::<DockerTesterError>()`.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::{DockerTesterError, TestPostgres};
+///
+/// # async fn run() {
+/// match TestPostgres::new("./migrations").await {
+///     Ok(test_postgres) => drop(test_postgres),
+///     Err(err) => match err.downcast_ref::<DockerTesterError>() {
+///         Some(DockerTesterError::Timeout { attempts, .. }) => println!("not ready after {attempts} attempts"),
+///         Some(DockerTesterError::ContainerStart(_) | DockerTesterError::Docker { .. }) => {
+///             println!("is docker running?")
+///         }
+///         Some(DockerTesterError::Migrate(_)) => println!("fix the migrations"),
+///         _ => panic!("{err}"),
+///     },
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub enum DockerTesterError {
+    /// the container couldn't be started or never became ready.
+    ContainerStart(anyhow::Error),
+    /// the server couldn't be connected to.
+    Connect(sqlx::Error),
+    /// the test database couldn't be created.
+    CreateDatabase(sqlx::Error),
+    /// the migrations couldn't be read or applied.
+    Migrate(MigrateError),
+    /// docker reported a port that isn't a number.
+    PortParse(String),
+    /// the container never became ready, retrying with a longer timeout may help.
+    Timeout {
+        id: String,
+        image: String,
+        attempts: u32,
+        /// why it wasn't ready at the last attempt.
+        reason: String,
+        /// the last log lines of the container.
+        logs: String,
+        /// every readiness check made, see StartReport::samples.
+        samples: Vec<ReadinessSample>,
+    },
+    /// a docker command failed, e.g. on a bad option or a stopped daemon.
+    Docker { stderr: String },
+    /// the registry has no such image or tag, retrying is pointless.
+    ImageNotFound { image: String, stderr: String },
+    /// docker printed output that couldn't be understood.
+    Parse(String),
+}
+
+impl DockerTesterError {
+    /// gets the typed error of a start, failures without a kind are ContainerStart.
+    pub(crate) fn from_start(err: anyhow::Error) -> Self {
+        err.downcast::<DockerTesterError>()
+            .unwrap_or_else(DockerTesterError::ContainerStart)
+    }
+}
+
+/// wraps the stderr of a failed docker command.
+pub(crate) fn docker_error(stderr: Vec<u8>) -> anyhow::Error {
+    DockerTesterError::Docker {
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+    }
+    .into()
+}
+
+impl fmt::Display for DockerTesterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DockerTesterError::ContainerStart(err) => write!(f, "cannot start the container: {err}"),
+            DockerTesterError::Connect(sqlx::Error::Database(db_err))
+                if db_err.code().as_deref() == Some("53300") =>
+            {
+                write!(
+                    f,
+                    "Postgres has too many clients ({}); raise TestPostgresBuilder::max_connections or lower the pool sizes",
+                    db_err.message()
+                )
+            }
+            DockerTesterError::Connect(err) => write!(f, "cannot connect to the server: {err}"),
+            DockerTesterError::CreateDatabase(err) => {
+                write!(f, "cannot create the database: {err}")
+            }
+            DockerTesterError::Migrate(MigrateError::VersionMismatch(version)) => write!(
+                f,
+                "migration {version} changed since it was applied; the reused database is stale — recreate it"
+            ),
+            DockerTesterError::Migrate(MigrateError::VersionMissing(version)) => write!(
+                f,
+                "migration {version} was applied but no longer exists; the reused database is stale — recreate it"
+            ),
+            DockerTesterError::Migrate(err) => write!(f, "cannot migrate the database: {err}"),
+            DockerTesterError::PortParse(port) => write!(f, "docker reported an invalid port {port:?}"),
+            DockerTesterError::Timeout {
+                image,
+                reason,
+                attempts,
+                logs,
+                ..
+            } => write!(
+                f,
+                "cannot start the image[{image}] container after {attempts} attempts: {reason}\nlast {} log lines:\n{logs}",
+                logs.lines().count()
+            ),
+            DockerTesterError::Docker { stderr } => write!(f, "{stderr}"),
+            DockerTesterError::ImageNotFound { image, stderr } => {
+                write!(f, "image not found: {image}: {}", stderr.trim())
+            }
+            DockerTesterError::Parse(output) => write!(f, "{output}"),
+        }
+    }
+}
+
+impl std::error::Error for DockerTesterError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DockerTesterError::ContainerStart(err) => Some(err.as_ref()),
+            DockerTesterError::Connect(err) | DockerTesterError::CreateDatabase(err) => Some(err),
+            DockerTesterError::Migrate(err) => Some(err),
+            DockerTesterError::PortParse(_)
+            | DockerTesterError::Timeout { .. }
+            | DockerTesterError::Docker { .. }
+            | DockerTesterError::ImageNotFound { .. }
+            | DockerTesterError::Parse(_) => None,
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use crate::wait::exec_output;
+use crate::Container;
+
+/// ExecOutput is the captured result of a command run with Container::exec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// the exit code of the command, -1 when docker was killed by a signal.
+    pub exit_code: i32,
+}
+
+impl ExecOutput {
+    /// tells whether the command exited with 0.
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+}
+
+impl Container {
+    /// Runs the command inside the container with `docker exec` and captures
+    /// its output. A command exiting with non-zero isn't an error, check
+    /// ExecOutput::exit_code, only failing to run docker is.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::start_container;
+    ///
+    /// let container = start_container("postgres:14-alpine", "5432", &["-e", "POSTGRES_PASSWORD=password"])
+    ///     .expect("Failed to start Postgres container");
+    /// let output = container
+    ///     .exec(&["pg_isready", "-U", "postgres"])
+    ///     .expect("Failed to run docker exec");
+    /// assert!(output.success(), "{}", output.stderr);
+    /// ```
+    pub fn exec(&self, cmd: &[&str]) -> Result<ExecOutput, anyhow::Error> {
+        let output = exec_output(&self.id, cmd)?;
+        Ok(ExecOutput {
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            exit_code: output.status.code().unwrap_or(-1),
+        })
+    }
+}
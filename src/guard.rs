@@ -0,0 +1,173 @@
+use std::ops::Deref;
+
+use crate::{
+    start_container_with_opts, stop_container_with_opts, Container, RemovalMode, StartOptions,
+    StopOptions,
+};
+
+/// ContainerGuard owns a started container and stops and removes it on drop,
+/// even when the test panics. It derefs to the Container.
+pub struct ContainerGuard {
+    container: Option<Container>,
+    stop_opts: StopOptions,
+    removal_mode: RemovalMode,
+    /// a reused container may serve other tests, so it's left running.
+    shared: bool,
+}
+
+impl ContainerGuard {
+    /// guards the container.
+    pub fn new(container: Container) -> Self {
+        Self {
+            container: Some(container),
+            stop_opts: StopOptions::default(),
+            removal_mode: RemovalMode::default(),
+            shared: false,
+        }
+    }
+
+    /// sets how the container is torn down on drop, e.g. StopOptions::remove
+    /// set to false only stops it, so a later phase can start it again.
+    pub fn stop_opts(mut self, stop_opts: StopOptions) -> Self {
+        self.stop_opts = stop_opts;
+        self
+    }
+
+    /// decides whether the container is removed on drop, defaults to RemovalMode::Always.
+    pub fn removal_mode(mut self, removal_mode: RemovalMode) -> Self {
+        self.removal_mode = removal_mode;
+        self
+    }
+
+    /// tells whether drop stops the container, given the current thread's state.
+    fn should_stop(&self) -> bool {
+        !self.shared && self.removal_mode.should_remove()
+    }
+
+    /// releases the container without stopping it.
+    pub fn into_inner(mut self) -> Container {
+        self.container
+            .take()
+            .expect("the guard always holds a container")
+    }
+}
+
+impl Deref for ContainerGuard {
+    type Target = Container;
+
+    fn deref(&self) -> &Container {
+        self.container
+            .as_ref()
+            .expect("the guard always holds a container")
+    }
+}
+
+impl Drop for ContainerGuard {
+    fn drop(&mut self) {
+        if let Some(container) = self.container.take() {
+            if self.shared {
+                debug!("Container[{}] kept for reuse", container.id);
+                return;
+            }
+            if !self.should_stop() {
+                info!(
+                    "Container[{}] kept, listening on {}:{}",
+                    container.id, container.host, container.port
+                );
+                return;
+            }
+            // a panic while panicking aborts, so only report the failure
+            match stop_container_with_opts(container.id.clone(), &self.stop_opts) {
+                Ok(()) => info!("Container[{}] dropped", container.id),
+                Err(err) => warn!("Container[{}] cannot be stopped: {err}", container.id),
+            }
+        }
+    }
+}
+
+/// Starts the specified container like start_container_with_opts, and guards it
+/// so it's stopped and removed when the guard is dropped. A container started
+/// with StartOptions::reuse is left running for the next test instead.
+///
+/// # Example
+/// ```no_run
+/// use docker_tester::{start_container_guarded, StartOptions};
+///
+/// let container = start_container_guarded("redis:7-alpine", "6379", &[], StartOptions::default())
+///     .expect("Failed to start the container");
+/// println!("Redis listens on {}:{}", container.host, container.port);
+/// // the container is removed here, or when the test panics
+/// ```
+pub fn start_container_guarded(
+    image: &str,
+    port: &str,
+    args: &[&str],
+    opts: StartOptions,
+) -> Result<ContainerGuard, anyhow::Error> {
+    let shared = opts.reuse;
+    let container = start_container_with_opts(image, port, args, opts)?;
+    Ok(ContainerGuard {
+        container: Some(container),
+        stop_opts: StopOptions::default(),
+        removal_mode: RemovalMode::default(),
+        shared,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::Protocol;
+
+    /// records whether the guard would stop its container while the thread unwinds.
+    struct Probe(Option<ContainerGuard>, Arc<AtomicBool>);
+
+    impl Drop for Probe {
+        fn drop(&mut self) {
+            let guard = self.0.take().unwrap();
+            self.1.store(guard.should_stop(), Ordering::SeqCst);
+            // no daemon to stop it with
+            guard.into_inner();
+        }
+    }
+
+    fn container() -> Container {
+        Container {
+            id: "0123456789ab".to_string(),
+            image: "redis:7-alpine".to_string(),
+            host: "127.0.0.1".to_string(),
+            port: 6379,
+            protocol: Protocol::Tcp,
+            ports: HashMap::new(),
+            ports_by_protocol: HashMap::new(),
+            name: None,
+        }
+    }
+
+    fn stops_while_panicking(removal_mode: RemovalMode) -> bool {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let probe_stopped = stopped.clone();
+        let result = std::thread::spawn(move || {
+            let guard = ContainerGuard::new(container()).removal_mode(removal_mode);
+            let _probe = Probe(Some(guard), probe_stopped);
+            panic!("the test failed");
+        })
+        .join();
+        assert!(result.is_err());
+        stopped.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn guard_should_follow_the_removal_mode_while_panicking() {
+        assert!(stops_while_panicking(RemovalMode::Always));
+        assert!(!stops_while_panicking(RemovalMode::OnSuccess));
+        assert!(!stops_while_panicking(RemovalMode::Never));
+        assert!(ContainerGuard::new(container())
+            .removal_mode(RemovalMode::OnSuccess)
+            .should_stop());
+    }
+}
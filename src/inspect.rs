@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::error::docker_error;
+use crate::runtime::docker_command;
+use crate::Container;
+
+/// ResourceLimits are the limits docker applied to a container, None when unlimited.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// the memory limit in bytes (`--memory`).
+    pub memory: Option<i64>,
+    /// the memory plus swap limit in bytes (`--memory-swap`), -1 is unlimited swap.
+    pub memory_swap: Option<i64>,
+    /// the CPUs in billionths of a CPU (`--cpus`), 1_500_000_000 is 1.5 CPUs.
+    pub nano_cpus: Option<i64>,
+    /// the relative CPU weight (`--cpu-shares`).
+    pub cpu_shares: Option<i64>,
+    /// the CPUs the container is pinned to (`--cpuset-cpus`).
+    pub cpuset_cpus: Option<String>,
+    /// the NUMA memory nodes the container is pinned to (`--cpuset-mems`).
+    pub cpuset_mems: Option<String>,
+    /// the maximum number of processes (`--pids-limit`).
+    pub pids_limit: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+struct HostConfig {
+    memory: i64,
+    memory_swap: i64,
+    nano_cpus: i64,
+    cpu_shares: i64,
+    cpuset_cpus: String,
+    cpuset_mems: String,
+    pids_limit: Option<i64>,
+}
+
+impl Container {
+    /// Gets the environment variables of the container from `.Config.Env`,
+    /// including the ones set by the image.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::start_container;
+    ///
+    /// let container = start_container("postgres:14-alpine", "5432", &["-e", "POSTGRES_PASSWORD=password"])
+    ///     .expect("Failed to start Postgres container");
+    /// let env = container.env().expect("Failed to inspect the container");
+    /// assert_eq!(env["POSTGRES_PASSWORD"], "password");
+    /// ```
+    pub fn env(&self) -> Result<HashMap<String, String>, anyhow::Error> {
+        let vars: Vec<String> = serde_json::from_str(&inspect(&self.id, "{{json .Config.Env}}")?)?;
+        Ok(parse_env(&vars))
+    }
+
+    /// Gets how many times docker restarted the container. Compare it at the start
+    /// and the end of a test to catch a service that crashed and came back.
+    pub fn restart_count(&self) -> Result<u32, anyhow::Error> {
+        Ok(inspect(&self.id, "{{.RestartCount}}")?.parse::<u32>()?)
+    }
+
+    /// Gets the names of the networks the container joined from
+    /// `.NetworkSettings.Networks`, sorted, e.g. `["bridge"]`.
+    pub fn networks(&self) -> Result<Vec<String>, anyhow::Error> {
+        parse_networks(&inspect(&self.id, "{{json .NetworkSettings.Networks}}")?)
+    }
+
+    /// Gets the resource limits docker applied from `.HostConfig`, so a test can
+    /// check a requested limit took effect, e.g. on a host without the cgroup
+    /// controller docker silently ignores it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::start_container;
+    ///
+    /// let container = start_container("redis:7-alpine", "6379", &["--memory=256m"])
+    ///     .expect("Failed to start Redis container");
+    /// let limits = container.resource_limits().expect("Failed to inspect the container");
+    /// assert_eq!(limits.memory, Some(256 * 1024 * 1024));
+    /// ```
+    pub fn resource_limits(&self) -> Result<ResourceLimits, anyhow::Error> {
+        parse_resource_limits(&inspect(&self.id, "{{json .HostConfig}}")?)
+    }
+}
+
+/// runs `docker inspect -f <tmpl>` on the container.
+pub(crate) fn inspect(id: &str, tmpl: &str) -> Result<String, anyhow::Error> {
+    let output = docker_command()
+        .arg("inspect")
+        .arg("-f")
+        .arg(tmpl)
+        .arg(id)
+        .output()?;
+    if !output.status.success() {
+        return Err(docker_error(output.stderr));
+    }
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+fn parse_env(vars: &[String]) -> HashMap<String, String> {
+    vars.iter()
+        .map(|var| match var.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => (var.to_string(), String::new()),
+        })
+        .collect()
+}
+
+fn parse_networks(output: &str) -> Result<Vec<String>, anyhow::Error> {
+    let networks: Option<HashMap<String, serde_json::Value>> = serde_json::from_str(output)?;
+    let mut names: Vec<String> = networks.unwrap_or_default().into_keys().collect();
+    names.sort();
+    Ok(names)
+}
+
+fn parse_resource_limits(output: &str) -> Result<ResourceLimits, anyhow::Error> {
+    let config: HostConfig = serde_json::from_str(output)?;
+    // docker reports an unset limit as 0 or empty
+    let limit = |value: i64| (value != 0).then_some(value);
+    let set = |value: String| (!value.is_empty()).then_some(value);
+    Ok(ResourceLimits {
+        memory: limit(config.memory),
+        memory_swap: limit(config.memory_swap),
+        nano_cpus: limit(config.nano_cpus),
+        cpu_shares: limit(config.cpu_shares),
+        cpuset_cpus: set(config.cpuset_cpus),
+        cpuset_mems: set(config.cpuset_mems),
+        pids_limit: config.pids_limit.and_then(limit),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_env_should_split_on_the_first_equal_sign() {
+        let vars = vec![
+            "POSTGRES_PASSWORD=pass=word".to_string(),
+            "PATH=/usr/bin".to_string(),
+            "EMPTY".to_string(),
+        ];
+        let env = parse_env(&vars);
+        assert_eq!(env["POSTGRES_PASSWORD"], "pass=word");
+        assert_eq!(env["PATH"], "/usr/bin");
+        assert_eq!(env["EMPTY"], "");
+    }
+
+    #[test]
+    fn parse_networks_should_return_sorted_names() {
+        let output = r#"{"bridge":{"IPAddress":"172.17.0.2"},"app":{"IPAddress":"172.18.0.2"}}"#;
+        assert_eq!(parse_networks(output).unwrap(), ["app", "bridge"]);
+        assert!(parse_networks("null").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_resource_limits_should_treat_zero_as_unlimited() {
+        let output = r#"{"Memory":268435456,"MemorySwap":536870912,"NanoCpus":1500000000,"CpuShares":0,"CpusetCpus":"0,1","CpusetMems":"","PidsLimit":null,"NetworkMode":"bridge"}"#;
+        assert_eq!(
+            parse_resource_limits(output).unwrap(),
+            ResourceLimits {
+                memory: Some(268435456),
+                memory_swap: Some(536870912),
+                nano_cpus: Some(1500000000),
+                cpuset_cpus: Some("0,1".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+}
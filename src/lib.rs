@@ -4,12 +4,12 @@
 //! ## Getting started
 //! You must have Docker installed and started
 //!
-//! ```rust
+//! ```rust,no_run
 //! use docker_tester::start_container;
 //!
 //! fn main() {
-//!     let image = "postgres:latest"
-//!     let port = "5432"
+//!     let image = "postgres:latest";
+//!     let port = "5432";
 //!     let args = &[
 //!         "-e",
 //!         "POSTGRES_USER=postgres",
@@ -17,16 +17,16 @@
 //!         "POSTGRES_PASSWORD=password"
 //!     ];
 //!     let container = start_container(image, port, args)
-//!         .expect("Failed to start Postgres contaienr");    
-//!     assert!(container.id);
-//!     assert!(container.host);
-//!     assert!(container.port);
+//!         .expect("Failed to start Postgres contaienr");
+//!     assert!(!container.id.is_empty());
+//!     assert!(!container.host.is_empty());
+//!     assert!(container.port > 0);
 //! }
 //! ```
 //!
 //! ## db-tester
 //!
-//! ```rust
+//! ```rust,no_run
 //! use docker_tester::TestPostgres;
 //!
 //! #[tokio::test]
@@ -39,28 +39,155 @@
 //!     // when test_postgres gets dropped, the database will be dropped on Docker
 //! }
 //! ```
+//!
+//! ## Sharing containers
+//!
+//! `TestSuite::global().shared_container(...)` starts a container once per
+//! test binary and stops it when the process exits, there is no teardown
+//! hook in the test harness for it otherwise. Under cargo-nextest every test
+//! is its own process, combine it with `StartOptions::reuse` to share there.
+//!
+//! ## Podman
+//!
+//! Set `DOCKER_TESTER_BIN` to run another docker-compatible binary instead
+//! of `docker` from the PATH, e.g. `podman` or `/usr/local/bin/docker`.
+//!
+//! ## Host
+//!
+//! The containers are dialed on the host docker reports for the mapped ports,
+//! with `0.0.0.0` replaced by `127.0.0.1`. When `DOCKER_HOST` points at a
+//! remote daemon, e.g. `tcp://docker.ci:2375` or `ssh://user@docker.ci`, the
+//! ports are published on that host, so it's dialed instead. Set
+//! `DOCKER_TESTER_HOST` to dial another host, it wins over both.
+//!
+//! ## Audit log
+//!
+//! When `DOCKER_TESTER_ARTIFACT` is set to a path, every container started and
+//! stopped appends a JSON line to that file with its id, image, ports, timestamp
+//! and status, e.g. to find leaked or slow containers of a CI run.
+//!
+//! ## Log bundle
+//!
+//! `dump_all_logs(dir)` writes the logs of every container still running to a
+//! file per container. When `DOCKER_TESTER_LOG_DIR` is set, the logs of the
+//! containers removed while a test panics are written there as well.
+//!
+//! ## Keeping failed containers
+//!
+//! Set `DOCKER_TESTER_KEEP_ON_FAILURE=1` to keep the containers the testers,
+//! ContainerGuard and TestEnv drop while a test panics, they print the container id and
+//! connection url so the leftover database can be inspected, e.g. with `psql`.
+//! Passing tests still remove theirs.
+
+#[macro_use]
+mod log;
 
+mod artifact;
+mod builder;
+pub use builder::ContainerBuilder;
 mod db_tester;
-pub use db_tester::TestPostgres;
+mod dump;
+#[cfg(feature = "cockroach")]
+pub use db_tester::TestCockroach;
+#[cfg(feature = "mssql")]
+pub use db_tester::TestMssql;
+#[cfg(feature = "mysql")]
+pub use db_tester::{start_mysql, TestMySQL};
+pub use db_tester::{start_postgres, TestPostgres, TestPostgresBuilder, TestRedis};
+pub use dump::dump_all_logs;
+mod env;
+pub use env::TestEnv;
+mod environment;
+pub use environment::{docker_environment, DockerEnvironment};
+mod error;
+pub use error::DockerTesterError;
+mod exec;
+pub use exec::ExecOutput;
+mod guard;
+pub use guard::{start_container_guarded, ContainerGuard};
+mod inspect;
+pub use inspect::ResourceLimits;
+mod limit;
+mod list;
+mod logs;
+pub use list::{cleanup_orphans, list_containers, ContainerInfo};
+pub use logs::{wait_for_log, LogFollower, LogReceiver};
+mod network;
+pub use network::{create_network, NetworkGuard};
+mod options;
+pub use options::{HealthcheckSpec, Mount, NetworkMode, RemovalMode, StartOptions, StopOptions};
+mod partition;
+mod ports;
+pub use ports::{PortMapping, Protocol};
+mod report;
+mod reuse;
+mod runtime;
+mod suite;
+pub use report::{ReadinessSample, StartReport};
+pub use suite::TestSuite;
+mod wait;
+pub use wait::WaitStrategy;
 
-use std::process::Command;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{thread, time};
 
 use serde::{Deserialize, Serialize};
 
+use error::docker_error;
+use runtime::{docker_binary, docker_command};
+
+/// LABEL marks every container started by this crate, so they can be listed.
+pub(crate) const LABEL: &str = "docker-tester";
+
+/// HOST_GATEWAY is the hostname containers use to reach the host,
+/// see StartOptions::host_gateway.
+pub const HOST_GATEWAY: &str = "host.docker.internal";
+
+/// Builds the url a container started with StartOptions::host_gateway uses to
+/// reach a server listening on port on the host, e.g. a mock of a third-party API.
+///
+/// # Example
+///
+/// ```
+/// use docker_tester::host_reachable_url;
+///
+/// assert_eq!(host_reachable_url(8080), "http://host.docker.internal:8080");
+/// ```
+pub fn host_reachable_url(port: u16) -> String {
+    format!("http://{HOST_GATEWAY}:{port}")
+}
+
 /// Container tracks information about the docker container started for tests.
+#[derive(Debug, Clone)]
 pub struct Container {
     pub id: String,
+    /// the image the container runs.
+    pub image: String,
     pub host: String,
     pub port: u16,
+    /// the protocol of port, from the port spec or StartOptions::protocol.
+    pub protocol: Protocol,
+    /// maps every requested container port to its host port, see host_port.
+    /// A number requested with both protocols maps to the first one's.
+    pub ports: HashMap<u16, u16>,
+    /// maps every requested container port and protocol to its host port,
+    /// see host_port_for.
+    pub ports_by_protocol: HashMap<(u16, Protocol), u16>,
+    /// the name set with StartOptions::name, None for a random docker name.
+    pub name: Option<String>,
 }
 
 /// Starts the specified container for running tests.
 ///
 /// # Example
-/// ```
-/// let image = "postgres:14-alpine"
-/// let port = "5432"
+/// ```no_run
+/// use docker_tester::start_container;
+///
+/// let image = "postgres:14-alpine";
+/// let port = "5432";
 /// let args = &[
 ///    "-e",
 ///    "POSTGRES_USER=postgres",
@@ -68,38 +195,319 @@ pub struct Container {
 ///    "POSTGRES_PASSWORD=password",
 /// ];
 /// let container = start_container(image, port, args).expect("Failed to start Postgres container");
-/// assert!(container.id);
-/// assert!(container.host);
-/// assert!(container.port);
-/// ```
-pub fn start_container(image: &str, port: &str, args: &[&str]) -> Result<Container, anyhow::Error> {
-    let output = Command::new("docker")
-        .arg("run")
-        .arg("-P")
-        .arg("-d")
-        .args(args)
-        .arg(&image)
-        .output()?;
-    if !output.status.success() {
-        return Err(anyhow::anyhow!(String::from_utf8(output.stderr)?));
+/// assert!(!container.id.is_empty());
+/// assert!(!container.host.is_empty());
+/// assert!(container.port > 0);
+/// ```
+pub fn start_container(
+    image: &str,
+    port: &str,
+    args: &[&str],
+) -> Result<Container, DockerTesterError> {
+    start_container_with_opts(image, port, args, StartOptions::default())
+        .map_err(DockerTesterError::from_start)
+}
+
+/// Starts the specified container, waiting for it as described by the StartOptions.
+///
+/// # Example
+/// ```no_run
+/// use docker_tester::{start_container_with_opts, StartOptions, WaitStrategy};
+///
+/// let opts = StartOptions {
+///     wait: WaitStrategy::FileExists("/tmp/ready".to_string()),
+///     ..Default::default()
+/// };
+/// let container = start_container_with_opts("my-service:latest", "8080", &[], opts)
+///     .expect("Failed to start the container");
+/// ```
+pub fn start_container_with_opts(
+    image: &str,
+    port: &str,
+    args: &[&str],
+    opts: StartOptions,
+) -> Result<Container, anyhow::Error> {
+    start_container_reported(image, port, args, opts).map(|(container, _)| container)
+}
+
+/// Starts the specified container like start_container, without blocking the
+/// async runtime during the pull and the readiness wait, e.g. to start several
+/// containers concurrently with `tokio::join!`.
+///
+/// # Example
+/// ```no_run
+/// use docker_tester::start_container_async;
+///
+/// # async fn run() {
+/// let (redis, nats) = tokio::join!(
+///     start_container_async("redis:7-alpine", "6379", &[]),
+///     start_container_async("nats:2", "4222", &[]),
+/// );
+/// let (redis, nats) = (redis.unwrap(), nats.unwrap());
+/// # }
+/// ```
+pub async fn start_container_async(
+    image: &str,
+    port: &str,
+    args: &[&str],
+) -> Result<Container, anyhow::Error> {
+    start_container_with_opts_async(image, port, args, StartOptions::default()).await
+}
+
+/// Starts the specified container like start_container_with_opts, without
+/// blocking the async runtime, see start_container_async.
+pub async fn start_container_with_opts_async(
+    image: &str,
+    port: &str,
+    args: &[&str],
+    opts: StartOptions,
+) -> Result<Container, anyhow::Error> {
+    let (owned_image, port) = (image.to_string(), port.to_string());
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    // the docker cli calls and the backoff sleeps run on the blocking pool,
+    // so every option of the sync start applies
+    tokio::task::spawn_blocking(move || {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        start_container_with_opts(&owned_image, &port, &args, opts)
+    })
+    .await
+    .map_err(|err| anyhow::anyhow!("the container start of the image[{image}] panicked: {err}"))?
+}
+
+/// starts the container mapping several ports like start_container_multi,
+/// without blocking the async runtime, see start_container_with_opts_async.
+#[cfg(feature = "cockroach")]
+pub(crate) async fn start_container_ports_async(
+    image: &str,
+    ports: &[&str],
+    args: &[&str],
+    opts: StartOptions,
+) -> Result<Container, anyhow::Error> {
+    let owned_image = image.to_string();
+    let ports: Vec<String> = ports.iter().map(|port| port.to_string()).collect();
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    tokio::task::spawn_blocking(move || {
+        let ports: Vec<&str> = ports.iter().map(String::as_str).collect();
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        start_container_ports(&owned_image, &ports, &args, opts, None)
+            .map(|(container, _)| container)
+    })
+    .await
+    .map_err(|err| anyhow::anyhow!("the container start of the image[{image}] panicked: {err}"))?
+}
+
+/// Starts the specified container like start_container_with_opts, and reports
+/// how long pulling, running and waiting for the container took.
+///
+/// # Example
+/// ```no_run
+/// use docker_tester::{start_container_reported, StartOptions};
+///
+/// let (container, report) =
+///     start_container_reported("redis:7-alpine", "6379", &[], StartOptions::default())
+///         .expect("Failed to start the container");
+/// println!("pulled: {}, ready after {:?}", report.pulled, report.wait_duration);
+/// ```
+pub fn start_container_reported(
+    image: &str,
+    port: &str,
+    args: &[&str],
+    opts: StartOptions,
+) -> Result<(Container, StartReport), anyhow::Error> {
+    start_container_ports(image, &[port], args, opts, None)
+}
+
+/// Starts the specified container like start_container_with_opts, and polls
+/// probe with the same backoff until it returns true, e.g. until the service
+/// inside accepts connections, which docker's running state doesn't tell.
+///
+/// # Example
+/// ```no_run
+/// use docker_tester::{start_container_with_probe, StartOptions};
+/// use std::net::TcpStream;
+///
+/// let container = start_container_with_probe(
+///     "my-service:latest",
+///     "8080",
+///     &[],
+///     StartOptions::default(),
+///     |container| TcpStream::connect((container.host.as_str(), container.port)).is_ok(),
+/// )
+/// .expect("Failed to start the container");
+/// ```
+pub fn start_container_with_probe(
+    image: &str,
+    port: &str,
+    args: &[&str],
+    opts: StartOptions,
+    mut probe: impl FnMut(&Container) -> bool,
+) -> Result<Container, anyhow::Error> {
+    start_container_ports(image, &[port], args, opts, Some(&mut probe))
+        .map(|(container, _)| container)
+}
+
+/// Starts the specified container and maps several of its ports, e.g. the
+/// HTTP and the gRPC ports of a service. Container::port is the first one,
+/// use Container::host_port to get the others. A port spec like `53/udp`
+/// picks the protocol, so a number can be mapped for both, see Container::host_port_for.
+///
+/// # Example
+/// ```no_run
+/// use docker_tester::start_container_multi;
+///
+/// let container = start_container_multi("my-service:latest", &["8080", "9090"], &[])
+///     .expect("Failed to start the container");
+/// let grpc_port = container.host_port(9090).expect("9090 is mapped");
+/// ```
+pub fn start_container_multi(
+    image: &str,
+    ports: &[&str],
+    args: &[&str],
+) -> Result<Container, anyhow::Error> {
+    start_container_ports(image, ports, args, StartOptions::default(), None)
+        .map(|(container, _)| container)
+}
+
+fn start_container_ports(
+    image: &str,
+    ports: &[&str],
+    args: &[&str],
+    opts: StartOptions,
+    probe: Option<&mut dyn FnMut(&Container) -> bool>,
+) -> Result<(Container, StartReport), anyhow::Error> {
+    let mut container_ports = Vec::new();
+    for port in ports {
+        container_ports.push(ports::parse_port_spec(port, opts.protocol)?);
     }
-    let output = String::from_utf8(output.stdout)?;
+    if container_ports.is_empty() {
+        return Err(anyhow::anyhow!("no container port to map"));
+    }
+    opts.validate()?;
+    if let Some(min_bytes) = opts.min_disk_space {
+        check_disk_space(min_bytes)?;
+    }
+    let mut report = StartReport::default();
 
-    let id = &output[..12];
-    let ns = extract_ip_and_port(id, port)?;
-    let host = format!("{}:{}", ns.host_ip, ns.host_port);
+    let reuse_key = opts.reuse.then(|| reuse::reuse_key(image, args, &opts));
+    let reused = match &reuse_key {
+        Some(key) => reuse::find_reusable(key, opts.reuse_only_if_running)?,
+        None => None,
+    };
+    let (id, started_here) = match reused {
+        Some(id) => (id, false),
+        None => (
+            docker_run(
+                image,
+                &container_ports,
+                args,
+                &opts,
+                reuse_key.as_deref(),
+                &mut report,
+            )?,
+            true,
+        ),
+    };
+    match wait_for_container(image, &id, &container_ports, &opts, probe, &mut report) {
+        Ok(container) => {
+            artifact::record_start(&container, &report.final_status);
+            dump::track(&container);
+            Ok((container, report))
+        }
+        Err(err) => {
+            // a failed start would otherwise leak the container and its slot,
+            // a reused one may serve other tests so it's left alone
+            if started_here {
+                if let Err(stop_err) = stop_container(id.clone()) {
+                    warn!("Container[{id}] cannot be stopped after the failed start: {stop_err}");
+                }
+            }
+            Err(err)
+        }
+    }
+}
+
+/// maps the ports of the started container and waits until it's ready.
+fn wait_for_container(
+    image: &str,
+    id: &str,
+    container_ports: &[(u16, Protocol)],
+    opts: &StartOptions,
+    mut probe: Option<&mut dyn FnMut(&Container) -> bool>,
+    report: &mut StartReport,
+) -> Result<Container, anyhow::Error> {
+    let override_host = environment::dial_host_override();
+    let mut mapped = HashMap::new();
+    let mut mapped_by_protocol = HashMap::new();
+    let mut first = None;
+    for &(container_port, protocol) in container_ports {
+        let (host_ip, host_port) = if opts.network_mode.maps_ports() {
+            let ns = extract_ip_and_port(id, &container_port.to_string(), protocol)?;
+            let host_port = ns
+                .host_port
+                .parse::<u16>()
+                .map_err(|_| DockerTesterError::PortParse(ns.host_port.clone()))?;
+            (
+                environment::dialable_host_ip(ns.host_ip, override_host.clone()),
+                host_port,
+            )
+        } else {
+            // the container listens on the docker host itself
+            (
+                override_host
+                    .clone()
+                    .unwrap_or_else(|| "127.0.0.1".to_string()),
+                container_port,
+            )
+        };
+        mapped.entry(container_port).or_insert(host_port);
+        mapped_by_protocol.insert((container_port, protocol), host_port);
+        first.get_or_insert((host_ip, host_port, protocol));
+    }
+    let (host_ip, host_port, protocol) = first.expect("at least one port is mapped");
+    let host = format!("{}:{}", host_ip, host_port);
+    let container = Container {
+        id: id.to_string(),
+        image: image.to_string(),
+        host: host_ip,
+        port: host_port,
+        protocol,
+        ports: mapped,
+        ports_by_protocol: mapped_by_protocol,
+        name: opts.name.clone(),
+    };
 
-    for i in 1..=10 {
-        let output = Command::new("docker")
+    let started = time::Instant::now();
+    let mut backoff = opts.backoff()?;
+    let readiness = wait::Readiness::new(&opts.wait, id)?;
+    loop {
+        let output = docker_command()
             .arg("inspect")
             .arg("-f")
-            .arg("{{.State.Status}}")
-            .arg(&id)
+            .arg("{{.State.Status}} {{if .State.Health}}{{.State.Health.Status}}{{end}}")
+            .arg(id)
             .output()?;
+        let at = time::Instant::now();
         let output = String::from_utf8(output.stdout)?;
-        let output = output.trim();
-        if output == "running" {
-            println!(
+        let (output, health) = parse_state(&output);
+        report.attempts = backoff.attempts();
+        report.final_status = output.to_string();
+        let not_ready = match output {
+            "running" => match readiness.not_ready_reason(id)? {
+                None if !probe.as_mut().is_none_or(|probe| probe(&container)) => {
+                    Some("the readiness probe is not satisfied".to_string())
+                }
+                not_ready => not_ready,
+            },
+            state => Some(format!("state {state}")),
+        };
+        report.samples.push(ReadinessSample {
+            at,
+            status: output.to_string(),
+            health: health.map(str::to_string),
+            error: not_ready.clone(),
+        });
+        if not_ready.is_none() {
+            info!(
                 r#"
 Docker Started
 Image:       {image}
@@ -108,82 +516,951 @@ Host:        {host}
                 "#
             );
             break;
-        } else {
-            if i == 10 {
-                return Err(anyhow::anyhow!("cannot start the image[{image}] container"));
+        }
+        match backoff.next_delay() {
+            Some(delay) => {
+                debug!("Container[{id}] state {output}, Watting for start");
+                thread::sleep(delay);
+            }
+            None => {
+                // the container may have crash-looped on a bad setting, its logs tell why
+                let logs = docker_logs(id, &["--tail", &FAILURE_LOG_LINES.to_string()])
+                    .unwrap_or_else(|err| format!("cannot get the logs: {err}"));
+                return Err(DockerTesterError::Timeout {
+                    id: id.to_string(),
+                    image: image.to_string(),
+                    attempts: backoff.attempts(),
+                    reason: not_ready.unwrap_or_default(),
+                    logs: logs.trim_end().to_string(),
+                    samples: std::mem::take(&mut report.samples),
+                }
+                .into());
             }
-            println!("Container[{id}] state {output}, Watting for start");
-            thread::sleep(time::Duration::from_secs(i));
         }
     }
+    if let Some(min_uptime) = opts.min_uptime {
+        let started_at = parse_started_at(&inspect::inspect(id, "{{.State.StartedAt}}")?)?;
+        // a clock skewed ahead of the daemon's counts as no uptime
+        let uptime = SystemTime::now()
+            .duration_since(started_at)
+            .unwrap_or_default();
+        if uptime < min_uptime {
+            info!(
+                "Container[{id}] ready, warming up for {:?}",
+                min_uptime - uptime
+            );
+            thread::sleep(min_uptime - uptime);
+        }
+    }
+    report.wait_duration = started.elapsed();
+    Ok(container)
+}
 
-    Ok(Container {
-        id: id.to_string(),
-        host: ns.host_ip,
-        port: ns.host_port.parse::<u16>().unwrap(),
-    })
+/// splits the `{{.State.Status}} {{.State.Health.Status}}` inspect output,
+/// the health is missing without a healthcheck.
+fn parse_state(output: &str) -> (&str, Option<&str>) {
+    let output = output.trim();
+    match output.split_once(' ') {
+        Some((status, health)) => (status, Some(health.trim())),
+        None => (output, None),
+    }
 }
 
-/// Stops and removes the specified container.
+/// pulls the image when it's missing and runs the container, returning its id.
+fn docker_run(
+    image: &str,
+    ports: &[(u16, Protocol)],
+    args: &[&str],
+    opts: &StartOptions,
+    reuse_key: Option<&str>,
+    report: &mut StartReport,
+) -> Result<String, anyhow::Error> {
+    // pull missing images up front, so the run phase is measured on its own
+    // and its output is only the container id
+    if !opts.skip_pull {
+        let started = time::Instant::now();
+        report.pulled = pull_if_missing(image, opts)?;
+        if report.pulled {
+            report.pull_duration = started.elapsed();
+        }
+    }
+
+    let started = time::Instant::now();
+    let mut cmd = docker_command();
+    cmd.arg("run").args(opts.publish_args(ports));
+    cmd.arg("-d")
+        .arg("--label")
+        .arg(format!("{LABEL}=true"))
+        .args(opts.run_args());
+    if let Some(key) = reuse_key {
+        cmd.arg("--label")
+            .arg(format!("{}={key}", reuse::reuse_label()));
+    }
+    // Docker Desktop has host.docker.internal built in, a native daemon needs the alias
+    if !opts.host_gateway
+        && opts.network_mode.maps_ports()
+        && docker_environment() == Some(DockerEnvironment::Native)
+    {
+        cmd.arg("--add-host")
+            .arg(format!("{HOST_GATEWAY}:host-gateway"));
+    }
+    if opts.run_as_host_user
+        && (!opts.mounts.is_empty() || !builder::bind_mount_host_paths(args).is_empty())
+    {
+        cmd.arg(format!("--user={}", host_user()?));
+    }
+    limit::reserve()?;
+    let output = match cmd.args(args).arg(image).args(&opts.command).output() {
+        Ok(output) => output,
+        Err(err) => {
+            limit::release();
+            return Err(err.into());
+        }
+    };
+    if !output.status.success() {
+        limit::release();
+        let stderr = String::from_utf8(output.stderr)?;
+        if opts.gpus.is_some() && is_gpu_error(&stderr) {
+            return Err(anyhow::anyhow!(
+                "cannot give the container GPUs, is the NVIDIA container toolkit installed? {}",
+                stderr.trim()
+            ));
+        }
+        if let Some(name) = opts.name.as_ref().filter(|_| is_name_conflict(&stderr)) {
+            return Err(anyhow::anyhow!(
+                "a container named {name} already exists, remove it with `docker rm -f {name}`"
+            ));
+        }
+        return Err(DockerTesterError::Docker { stderr }.into());
+    }
+    let output = String::from_utf8(output.stdout)?;
+    report.run_duration = started.elapsed();
+
+    let id = match parse_container_id(&output) {
+        Ok(id) => id,
+        Err(err) => {
+            limit::release();
+            return Err(err);
+        }
+    };
+    limit::assign(&id);
+    if let Some(lifetime) = opts.max_lifetime {
+        if let Err(err) = spawn_reaper(&id, lifetime) {
+            if let Err(stop_err) = stop_container(id.clone()) {
+                warn!("Container[{id}] cannot be stopped after the failed start: {stop_err}");
+            }
+            return Err(err);
+        }
+    }
+    Ok(id)
+}
+
+/// tells whether `docker run --gpus` failed because the daemon has no GPU runtime.
+fn is_gpu_error(stderr: &str) -> bool {
+    stderr.contains("could not select device driver")
+}
+
+/// tells whether `docker run --name` failed because another container has the name.
+fn is_name_conflict(stderr: &str) -> bool {
+    stderr.contains("is already in use by container")
+}
+
+/// gets the short id from the `docker run -d` output, the full id on the last line.
+fn parse_container_id(output: &str) -> Result<String, anyhow::Error> {
+    let id = output.trim().lines().last().unwrap_or_default().trim();
+    if id.len() < 12 || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(
+            DockerTesterError::Parse(format!("unexpected docker run output: {output}")).into(),
+        );
+    }
+    Ok(id[..12].to_string())
+}
+
+/// Starts the first of the images that can be pulled and started, e.g. a primary
+/// image followed by a mirror, so a registry outage doesn't fail the whole suite.
+/// Container::image tells which one was used, the error has every image's.
 ///
 /// # Example
+/// ```no_run
+/// use docker_tester::{start_container_with_fallbacks, StartOptions};
 ///
+/// let images = &["postgres:14-alpine", "mirror.gcr.io/library/postgres:14-alpine"];
+/// let container = start_container_with_fallbacks(images, "5432", &[], StartOptions::default())
+///     .expect("Failed to start Postgres container");
+/// println!("started {}", container.image);
 /// ```
-/// let container_id = "dfd60e4ef0c0";
+pub fn start_container_with_fallbacks(
+    images: &[&str],
+    port: &str,
+    args: &[&str],
+    opts: StartOptions,
+) -> Result<Container, anyhow::Error> {
+    let mut errors = Vec::new();
+    // the start pulls a missing image itself, unless StartOptions::skip_pull is set
+    for image in images {
+        match start_container_with_opts(image, port, args, opts.clone()) {
+            Ok(container) => return Ok(container),
+            Err(err) => {
+                warn!("Cannot start the image[{image}]: {err}");
+                errors.push(format!("{image}: {err}"));
+            }
+        }
+    }
+    Err(anyhow::anyhow!(
+        "cannot start any of the images: {}",
+        errors.join("; ")
+    ))
+}
+
+/// pulls the image when it's not available locally for the platform,
+/// telling whether it did.
+fn pull_if_missing(image: &str, opts: &StartOptions) -> Result<bool, anyhow::Error> {
+    let platform = opts.platform.as_deref();
+    let inspect = docker_command()
+        .arg("image")
+        .arg("inspect")
+        .arg("-f")
+        .arg("{{.Os}}/{{.Architecture}}{{if .Variant}}/{{.Variant}}{{end}}")
+        .arg(image)
+        .output()?;
+    if inspect.status.success() {
+        let local = String::from_utf8(inspect.stdout)?;
+        if platform.is_none_or(|platform| same_platform(local.trim(), platform)) {
+            return Ok(false);
+        }
+    }
+    let mut cmd = docker_command();
+    cmd.arg("pull");
+    if let Some(platform) = platform {
+        cmd.arg(format!("--platform={platform}"));
+    }
+    if opts.pull_quiet {
+        cmd.arg("--quiet");
+    }
+    cmd.arg(image);
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut child = cmd.spawn()?;
+    let mut pulled = None;
+    let mut stdout = String::new();
+    if let Some(out) = child.stdout.take() {
+        for line in BufReader::new(out).lines() {
+            let line = line?;
+            if !opts.pull_quiet {
+                // show the progress while reading the status line
+                info!("{line}");
+            }
+            pulled = pulled.or(parse_pull_status(&line));
+            stdout.push_str(&line);
+            stdout.push('\n');
+        }
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if is_image_not_found(&stderr) {
+            return Err(DockerTesterError::ImageNotFound {
+                image: image.to_string(),
+                stderr: stderr.into_owned(),
+            }
+            .into());
+        }
+        return Err(anyhow::anyhow!(
+            "cannot pull the image {image}: {stdout}{}",
+            stderr.trim()
+        ));
+    }
+    // --quiet only prints the image name, the image was missing so it was downloaded
+    Ok(pulled.unwrap_or(true))
+}
+
+/// tells whether `docker pull` failed because the registry has no such image or tag.
+fn is_image_not_found(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    [
+        "manifest unknown",
+        "not found",
+        "does not exist",
+        "pull access denied",
+    ]
+    .iter()
+    .any(|reason| stderr.contains(reason))
+}
+
+/// reads the `Status:` line of `docker pull`, telling whether the image was downloaded.
+fn parse_pull_status(line: &str) -> Option<bool> {
+    let status = line.trim().strip_prefix("Status: ")?;
+    if status.starts_with("Downloaded newer image") {
+        Some(true)
+    } else if status.starts_with("Image is up to date") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// compares platforms, a platform without variant matches any variant.
+fn same_platform(local: &str, wanted: &str) -> bool {
+    local == wanted
+        || (wanted.matches('/').count() == 1 && local.starts_with(&format!("{wanted}/")))
+}
+
+/// Stops and removes the specified container.
+///
+/// A container that doesn't exist anymore, e.g. one already stopped or started
+/// with `--rm`, is treated as stopped.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::stop_container;
+///
+/// let container_id = "dfd60e4ef0c0".to_string();
 /// stop_container(container_id).expect("Failed to stop the container");
 /// ```
 pub fn stop_container(id: String) -> Result<(), anyhow::Error> {
-    let output = Command::new("docker").arg("stop").arg(&id).output()?;
+    stop_container_with_opts(id, &StopOptions::default())
+}
+
+/// Stops and removes the specified container as described by the StopOptions.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::{stop_container_with_opts, StopOptions};
+///
+/// let opts = StopOptions {
+///     remove_volumes: false,
+///     ..Default::default()
+/// };
+/// stop_container_with_opts("dfd60e4ef0c0".to_string(), &opts)
+///     .expect("Failed to stop the container");
+/// ```
+pub fn stop_container_with_opts(id: String, opts: &StopOptions) -> Result<(), anyhow::Error> {
+    docker_stop(&id)?;
+    if !opts.remove {
+        return Ok(());
+    }
+    docker_rm(&id, opts)
+}
+
+/// Stops the specified container without removing it, unlike stop_container,
+/// so a later phase of the test can `docker start` it again with its data intact.
+/// Stop it with stop_container once it's not needed anymore.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::{stop_container, stop_only};
+///
+/// stop_only("dfd60e4ef0c0").expect("Failed to stop the container");
+/// // restart it with `docker start dfd60e4ef0c0` and check the data survived
+/// stop_container("dfd60e4ef0c0".to_string()).expect("Failed to remove the container");
+/// ```
+pub fn stop_only(id: &str) -> Result<(), anyhow::Error> {
+    let result = docker_stop(id);
+    artifact::record_stop(id, "stop", &result);
+    result
+}
+
+/// Stops the specified container and returns its exit code before removing it,
+/// so a test can assert the service shut down cleanly.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::stop_and_get_exit_code;
+///
+/// let exit_code = stop_and_get_exit_code("dfd60e4ef0c0".to_string())
+///     .expect("Failed to stop the container");
+/// assert_eq!(exit_code, 0);
+/// ```
+pub fn stop_and_get_exit_code(id: String) -> Result<i64, anyhow::Error> {
+    docker_stop(&id)?;
+    let output = docker_command()
+        .arg("inspect")
+        .arg("-f")
+        .arg("{{.State.ExitCode}}")
+        .arg(&id)
+        .output()?;
+    if !output.status.success() {
+        return Err(docker_error(output.stderr));
+    }
+    let exit_code = String::from_utf8(output.stdout)?.trim().parse::<i64>()?;
+    docker_rm(&id, &StopOptions::default())?;
+    Ok(exit_code)
+}
+
+/// gets the `uid:gid` of the test process.
+fn host_user() -> Result<String, anyhow::Error> {
+    let id = |flag: &str| -> Result<String, anyhow::Error> {
+        let output = Command::new("id").arg(flag).output()?;
+        if !output.status.success() {
+            return Err(docker_error(output.stderr));
+        }
+        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    };
+    Ok(format!("{}:{}", id("-u")?, id("-g")?))
+}
+
+/// starts a detached shell force-removing the container after lifetime, it
+/// isn't tied to this process so it also fires when the process is killed.
+fn spawn_reaper(id: &str, lifetime: time::Duration) -> Result<(), anyhow::Error> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(reaper_script(docker_binary(), id, lifetime))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // reap the shell when it exits while this process is still running
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+fn reaper_script(docker: &str, id: &str, lifetime: time::Duration) -> String {
+    // quote the binary, its path may have spaces
+    let docker = format!("'{}'", docker.replace('\'', r"'\''"));
+    format!(
+        "sleep {:.3}; {docker} rm -f -v {id}",
+        lifetime.as_secs_f64()
+    )
+}
+
+fn docker_stop(id: &str) -> Result<(), anyhow::Error> {
+    let output = docker_command().arg("stop").arg(id).output()?;
+    if !output.status.success() {
+        // already removed, e.g. a `--rm` container or a second stop
+        if is_no_such_container(&String::from_utf8_lossy(&output.stderr)) {
+            debug!("Container[{id}] already removed");
+            return Ok(());
+        }
+        return Err(docker_error(output.stderr));
+    }
+    Ok(())
+}
+
+fn docker_rm(id: &str, opts: &StopOptions) -> Result<(), anyhow::Error> {
+    dump::dump_on_panic(id);
+    let result = remove(id, opts);
+    artifact::record_stop(id, "remove", &result);
+    if result.is_ok() {
+        dump::untrack(id);
+    }
+    result
+}
+
+fn remove(id: &str, opts: &StopOptions) -> Result<(), anyhow::Error> {
+    let mut cmd = docker_command();
+    cmd.arg("rm").arg(id);
+    if opts.remove_volumes {
+        cmd.arg("-v");
+    }
+    let output = cmd.output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8(output.stderr)?;
+        if is_no_such_container(&stderr) {
+            // a second removal finds the slot released already
+            limit::release_container(id);
+            return Ok(());
+        }
+        if !opts.remove_volumes || !is_volume_error(&stderr) {
+            return Err(anyhow::anyhow!(stderr));
+        }
+        // a volume still used by another container can't be removed, keep it
+        warn!("Container[{id}] volumes kept: {}", stderr.trim());
+        let output = docker_command().arg("rm").arg(id).output()?;
+        let stderr = String::from_utf8(output.stderr)?;
+        // the daemon may have removed the container before failing on the volume
+        if !output.status.success() && !is_no_such_container(&stderr) {
+            return Err(anyhow::anyhow!(stderr));
+        }
+    }
+    limit::release_container(id);
+    Ok(())
+}
+
+/// tells whether a docker command failed because the container doesn't exist.
+fn is_no_such_container(stderr: &str) -> bool {
+    stderr.to_lowercase().contains("no such container")
+}
+
+/// tells whether `docker rm -v` failed on removing a volume rather than the container.
+fn is_volume_error(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    stderr.contains("volume is in use") || stderr.contains("unable to remove volume")
+}
+
+/// Checks the docker data root has at least min_bytes of free disk space,
+/// so a data-heavy container fails up front instead of in the middle of a test.
+///
+/// The free space is read with `df` on the `DockerRootDir` reported by `docker info`.
+/// When the daemon runs elsewhere (a VM or a remote host) the directory isn't
+/// visible locally and the check is skipped.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::check_disk_space;
+///
+/// check_disk_space(2 * 1024 * 1024 * 1024).expect("Not enough disk for the container");
+/// ```
+pub fn check_disk_space(min_bytes: u64) -> Result<(), anyhow::Error> {
+    let output = docker_command()
+        .arg("info")
+        .arg("-f")
+        .arg("{{.DockerRootDir}}")
+        .output()?;
     if !output.status.success() {
-        return Err(anyhow::anyhow!(String::from_utf8(output.stderr)?));
+        return Err(docker_error(output.stderr));
+    }
+    let root_dir = String::from_utf8(output.stdout)?.trim().to_string();
+    if !std::path::Path::new(&root_dir).exists() {
+        warn!("Docker root dir {root_dir} is not local, skipping the disk space check");
+        return Ok(());
     }
 
-    let output = Command::new("docker")
+    let output = Command::new("df").arg("-Pk").arg(&root_dir).output()?;
+    if !output.status.success() {
+        return Err(docker_error(output.stderr));
+    }
+    let available = parse_df_available(&String::from_utf8(output.stdout)?)
+        .ok_or_else(|| anyhow::anyhow!("cannot read the free disk space of {root_dir}"))?;
+    if available < min_bytes {
+        return Err(anyhow::anyhow!(
+            "insufficient disk for container: {available} bytes free in {root_dir}, {min_bytes} required"
+        ));
+    }
+    Ok(())
+}
+
+/// reads the available bytes from `df -Pk` output.
+fn parse_df_available(output: &str) -> Option<u64> {
+    let line = output.lines().nth(1)?;
+    let kilobytes = line.split_whitespace().nth(3)?.parse::<u64>().ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Checks whether the named volume exists, e.g. to skip seeding a volume
+/// already populated by a previous test run.
+pub fn volume_exists(name: &str) -> Result<bool, anyhow::Error> {
+    let output = docker_command()
+        .arg("volume")
+        .arg("inspect")
+        .arg(name)
+        .output()?;
+    Ok(output.status.success())
+}
+
+/// Removes the named volume, invalidating the data persisted in it.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::remove_volume;
+///
+/// remove_volume("docker-tester-fixtures").expect("Failed to remove the volume");
+/// ```
+pub fn remove_volume(name: &str) -> Result<(), anyhow::Error> {
+    let output = docker_command()
+        .arg("volume")
         .arg("rm")
-        .arg(&id)
-        .arg("-v")
+        .arg(name)
         .output()?;
     if !output.status.success() {
-        return Err(anyhow::anyhow!(String::from_utf8(output.stderr)?));
+        return Err(docker_error(output.stderr));
     }
     Ok(())
 }
 
-fn extract_ip_and_port(id: &str, port: &str) -> Result<NetworkSettings, anyhow::Error> {
-    let tmpl = format!(
-        r#"'[{{{{range $k,$v := (index .NetworkSettings.Ports "{port}/tcp")}}}}{{{{json $v}}}}{{{{end}}}}]'"#
-    );
-    let output = Command::new("docker")
+/// gets the host of a remote docker daemon set through `DOCKER_HOST`.
+pub(crate) fn remote_docker_host() -> Option<String> {
+    parse_remote_docker_host(&std::env::var("DOCKER_HOST").ok()?)
+}
+
+fn parse_remote_docker_host(docker_host: &str) -> Option<String> {
+    let address = docker_host
+        .strip_prefix("tcp://")
+        .or_else(|| docker_host.strip_prefix("ssh://"))?;
+    let address = address.split('/').next()?;
+    let address = address.rsplit('@').next()?;
+    let host = match address.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next()?,
+        None => address.split(':').next()?,
+    };
+    match host {
+        "" | "localhost" | "127.0.0.1" | "::1" => None,
+        host => Some(host.to_string()),
+    }
+}
+
+/// FAILURE_LOG_LINES is how many log lines the error of a container that never became ready has.
+const FAILURE_LOG_LINES: usize = 50;
+
+/// Gets all the logs of the specified container, with stdout followed by stderr.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::container_logs;
+///
+/// let logs = container_logs("dfd60e4ef0c0").expect("Failed to get logs");
+/// ```
+pub fn container_logs(id: &str) -> Result<String, anyhow::Error> {
+    docker_logs(id, &[])
+}
+
+/// Gets the logs the specified container produced since the given time,
+/// with stdout followed by stderr.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::container_logs_since;
+/// use std::time::SystemTime;
+///
+/// let checkpoint = SystemTime::now();
+/// // do something with the container
+/// let logs = container_logs_since("dfd60e4ef0c0", checkpoint).expect("Failed to get logs");
+/// ```
+pub fn container_logs_since(id: &str, since: SystemTime) -> Result<String, anyhow::Error> {
+    docker_logs(id, &["--since", &docker_timestamp(since)?])
+}
+
+/// runs `docker logs <args> <id>`.
+fn docker_logs(id: &str, args: &[&str]) -> Result<String, anyhow::Error> {
+    let output = docker_command().arg("logs").args(args).arg(id).output()?;
+    if !output.status.success() {
+        return Err(docker_error(output.stderr));
+    }
+    let mut logs = String::from_utf8(output.stdout)?;
+    logs.push_str(&String::from_utf8(output.stderr)?);
+    Ok(logs)
+}
+
+/// parses the RFC 3339 UTC time of `.State.StartedAt`, e.g. `2022-11-28T05:55:05.123456789Z`.
+fn parse_started_at(started_at: &str) -> Result<SystemTime, anyhow::Error> {
+    let invalid = || anyhow::anyhow!("unexpected container start time {started_at:?}");
+    let (date, time) = started_at
+        .trim()
+        .strip_suffix('Z')
+        .and_then(|started_at| started_at.split_once('T'))
+        .ok_or_else(invalid)?;
+    let date: Vec<i64> = date
+        .split('-')
+        .map(|part| part.parse::<i64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid())?;
+    let (time, fraction) = time.split_once('.').unwrap_or((time, "0"));
+    let time: Vec<u64> = time
+        .split(':')
+        .map(|part| part.parse::<u64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid())?;
+    let (&[year, month, day], &[hour, minute, second]) = (date.as_slice(), time.as_slice()) else {
+        return Err(invalid());
+    };
+    let nanos = format!("{fraction:0<9}")[..9]
+        .parse::<u32>()
+        .map_err(|_| invalid())?;
+    // days from civil, see http://howardhinnant.github.io/date_algorithms.html
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = u64::try_from(era * 146_097 + day_of_era - 719_468).map_err(|_| invalid())?;
+    let secs = days * 86_400 + hour * 3_600 + minute * 60 + second;
+    Ok(UNIX_EPOCH + time::Duration::new(secs, nanos))
+}
+
+/// formats the time as a unix timestamp understood by `docker logs --since`.
+fn docker_timestamp(time: SystemTime) -> Result<String, anyhow::Error> {
+    let since = time.duration_since(UNIX_EPOCH)?;
+    Ok(format!("{}.{:09}", since.as_secs(), since.subsec_nanos()))
+}
+
+pub(crate) fn extract_ip_and_port(
+    id: &str,
+    port: &str,
+    protocol: Protocol,
+) -> Result<NetworkSettings, anyhow::Error> {
+    let output = docker_command()
         .arg("inspect")
         .arg("-f")
-        .arg(tmpl)
-        .arg(&id)
+        .arg(port_template(port, protocol))
+        .arg(id)
         .output()?;
     if !output.status.success() {
-        return Err(anyhow::anyhow!(String::from_utf8(output.stderr)?));
+        return Err(docker_error(output.stderr));
     }
 
     let json_string = String::from_utf8(output.stdout)?;
-    let datas: Vec<NetworkSettings> = serde_json::from_str(&json_string.trim().trim_matches('\''))?;
-    assert!(
-        datas.len() >= 1,
-        "The container[{id}] cannnot find NetworkSettings.Ports"
-    );
-    let mut network_settings = NetworkSettings::default();
-    if let Some(ns) = datas.first() {
-        network_settings.host_ip = ns.host_ip.clone();
-        network_settings.host_port = ns.host_port.clone();
+    if let Some(ns) = parse_port_bindings(&json_string) {
+        return Ok(ns);
+    }
+
+    // the inspect output differs across docker versions, `docker port` is simpler
+    let output = docker_command()
+        .arg("port")
+        .arg(id)
+        .arg(format!("{port}/{protocol}"))
+        .output()?;
+    if !output.status.success() {
+        return Err(docker_error(output.stderr));
     }
-    Ok(network_settings)
+    parse_docker_port(&String::from_utf8(output.stdout)?)
+        .ok_or_else(|| anyhow::anyhow!("The container[{id}] cannnot find NetworkSettings.Ports"))
+}
+
+/// the inspect template of the bindings of the `port/protocol` key.
+fn port_template(port: &str, protocol: Protocol) -> String {
+    format!(r#"{{{{json (index .NetworkSettings.Ports "{port}/{protocol}")}}}}"#)
+}
+
+/// parses the inspect json of the bindings of one port, taking the first one.
+fn parse_port_bindings(output: &str) -> Option<NetworkSettings> {
+    let datas: Option<Vec<NetworkSettings>> =
+        serde_json::from_str(output.trim()).unwrap_or_default();
+    datas.and_then(|datas| datas.into_iter().next())
+}
+
+/// parses `docker port` output such as `0.0.0.0:49153`, preferring IPv4 bindings.
+fn parse_docker_port(output: &str) -> Option<NetworkSettings> {
+    let bindings: Vec<NetworkSettings> = output
+        .lines()
+        .filter_map(|line| {
+            let (host_ip, host_port) = line.trim().rsplit_once(':')?;
+            let host_ip = host_ip.trim_start_matches('[').trim_end_matches(']');
+            host_port.parse::<u16>().ok()?;
+            Some(NetworkSettings {
+                host_ip: host_ip.to_string(),
+                host_port: host_port.to_string(),
+            })
+        })
+        .collect();
+    let ipv4 = bindings.iter().position(|ns| !ns.host_ip.contains(':'));
+    bindings.into_iter().nth(ipv4.unwrap_or(0))
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
-struct NetworkSettings {
+pub(crate) struct NetworkSettings {
     #[serde(alias = "HostIp")]
-    host_ip: String,
+    pub(crate) host_ip: String,
 
     #[serde(alias = "HostPort")]
-    host_port: String,
+    pub(crate) host_port: String,
+}
+
+#[test]
+fn parse_container_id_should_reject_unexpected_output() {
+    let full_id = "dfd60e4ef0c0a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a";
+    assert_eq!(
+        parse_container_id(&format!("  {full_id}\n")).unwrap(),
+        "dfd60e4ef0c0"
+    );
+    assert_eq!(
+        parse_container_id(&format!("WARNING: something\n{full_id}\n")).unwrap(),
+        "dfd60e4ef0c0"
+    );
+    let err = parse_container_id("abc\n").unwrap_err();
+    assert_eq!(err.to_string(), "unexpected docker run output: abc\n");
+    assert!(matches!(
+        err.downcast_ref::<DockerTesterError>(),
+        Some(DockerTesterError::Parse(_))
+    ));
+    assert!(parse_container_id("").is_err());
+    assert!(parse_container_id("not-a-container-id").is_err());
+}
+
+#[test]
+fn is_volume_error_should_only_match_volume_removal() {
+    assert!(is_volume_error(
+        "Error response from daemon: unable to remove volume: remove fixtures: volume is in use - [0123456789ab]"
+    ));
+    assert!(!is_volume_error(
+        "Error response from daemon: No such container: 0123456789ab"
+    ));
+}
+
+#[test]
+fn is_no_such_container_should_match_docker_and_podman() {
+    assert!(is_no_such_container(
+        "Error response from daemon: No such container: 0123456789ab"
+    ));
+    assert!(is_no_such_container(
+        "Error: no such container 0123456789ab"
+    ));
+    assert!(!is_no_such_container(
+        "Error response from daemon: cannot stop container: 0123456789ab: permission denied"
+    ));
+}
+
+#[test]
+fn port_template_should_use_the_protocol() {
+    assert_eq!(
+        port_template("8125", Protocol::Udp),
+        r#"{{json (index .NetworkSettings.Ports "8125/udp")}}"#
+    );
+    let ns = parse_port_bindings(r#"[{"HostIp":"0.0.0.0","HostPort":"49160"}]"#).unwrap();
+    assert_eq!(ns.host_port, "49160");
+    assert!(parse_port_bindings("null").is_none());
+}
+
+#[test]
+fn parse_started_at_should_read_docker_times() {
+    assert_eq!(
+        parse_started_at("2022-11-28T01:46:40.000000005Z").unwrap(),
+        UNIX_EPOCH + time::Duration::new(1_669_600_000, 5)
+    );
+    assert_eq!(
+        parse_started_at("1970-01-01T00:00:01.5Z").unwrap(),
+        UNIX_EPOCH + time::Duration::from_millis(1500)
+    );
+    assert_eq!(
+        parse_started_at("2024-03-01T00:00:00Z").unwrap(),
+        UNIX_EPOCH + time::Duration::from_secs(1_709_251_200)
+    );
+    assert!(parse_started_at("0001-01-01T00:00:00Z").is_err());
+    assert!(parse_started_at("yesterday").is_err());
+}
+
+#[test]
+fn docker_timestamp_should_keep_nanos() {
+    let time = UNIX_EPOCH + time::Duration::new(1_669_600_000, 5);
+    assert_eq!(docker_timestamp(time).unwrap(), "1669600000.000000005");
+}
+
+#[test]
+fn parse_df_available_should_read_the_available_column() {
+    let output = "Filesystem     1024-blocks      Used Available Capacity Mounted on
+/dev/sda1        102400000  51200000  40960000      56% /var/lib/docker
+";
+    assert_eq!(parse_df_available(output), Some(40960000 * 1024));
+    assert_eq!(parse_df_available(""), None);
+}
+
+#[test]
+fn parse_remote_docker_host_should_skip_local_daemons() {
+    assert_eq!(
+        parse_remote_docker_host("tcp://docker.ci:2375"),
+        Some("docker.ci".to_string())
+    );
+    assert_eq!(
+        parse_remote_docker_host("ssh://user@10.0.0.5:22"),
+        Some("10.0.0.5".to_string())
+    );
+    assert_eq!(parse_remote_docker_host("tcp://127.0.0.1:2375"), None);
+    assert_eq!(
+        parse_remote_docker_host("unix:///var/run/docker.sock"),
+        None
+    );
+}
+
+#[test]
+fn parse_docker_port_should_prefer_ipv4() {
+    let ns = parse_docker_port(":::49153\n0.0.0.0:49153\n").unwrap();
+    assert_eq!(ns.host_ip, "0.0.0.0");
+    assert_eq!(ns.host_port, "49153");
+
+    let ns = parse_docker_port("[::]:49154\n").unwrap();
+    assert_eq!(ns.host_ip, "::");
+    assert_eq!(ns.host_port, "49154");
+
+    assert!(parse_docker_port("").is_none());
+    assert!(parse_docker_port("Error: No public port '5432/tcp' published").is_none());
+}
+
+#[test]
+fn same_platform_should_match_any_variant_when_unset() {
+    assert!(same_platform("linux/amd64", "linux/amd64"));
+    assert!(same_platform("linux/arm/v7", "linux/arm"));
+    assert!(!same_platform("linux/arm/v6", "linux/arm/v7"));
+    assert!(!same_platform("linux/arm64", "linux/amd64"));
+}
+
+#[test]
+fn parse_pull_status_should_tell_a_download() {
+    assert_eq!(
+        parse_pull_status("Status: Downloaded newer image for redis:7-alpine"),
+        Some(true)
+    );
+    assert_eq!(
+        parse_pull_status("Status: Image is up to date for redis:7-alpine"),
+        Some(false)
+    );
+    assert_eq!(
+        parse_pull_status("7-alpine: Pulling from library/redis"),
+        None
+    );
+}
+
+#[test]
+fn is_image_not_found_should_match_a_missing_tag() {
+    assert!(is_image_not_found(
+        "Error response from daemon: manifest for postgres:99 not found: manifest unknown"
+    ));
+    assert!(is_image_not_found(
+        "Error response from daemon: pull access denied for nope, repository does not exist"
+    ));
+    assert!(!is_image_not_found(
+        "Error response from daemon: Get \"https://registry-1.docker.io/v2/\": net/http: TLS handshake timeout"
+    ));
+}
+
+#[test]
+fn is_gpu_error_should_match_a_missing_toolkit() {
+    assert!(is_gpu_error(
+        r#"docker: Error response from daemon: could not select device driver "" with capabilities: [[gpu]]."#
+    ));
+    assert!(!is_gpu_error(
+        "docker: Error response from daemon: No such image"
+    ));
+}
+
+#[test]
+fn start_container_should_report_a_missing_image() {
+    let err = start_container("docker-tester/does-not-exist:never", "80", &[]).unwrap_err();
+    assert!(
+        matches!(err, DockerTesterError::ImageNotFound { .. }),
+        "{err}"
+    );
+}
+
+#[test]
+fn parse_state_should_split_the_health() {
+    assert_eq!(
+        parse_state("running starting\n"),
+        ("running", Some("starting"))
+    );
+    assert_eq!(parse_state("created \n"), ("created", None));
+    assert_eq!(parse_state("running "), ("running", None));
+    assert_eq!(parse_state("exited"), ("exited", None));
+}
+
+#[test]
+fn is_name_conflict_should_match_a_taken_name() {
+    assert!(is_name_conflict(
+        r#"docker: Error response from daemon: Conflict. The container name "/orders-pg" is already in use by container "dfd60e4ef0c0". You have to remove (or rename) that container to be able to reuse that name."#
+    ));
+    assert!(!is_name_conflict(
+        "docker: Error response from daemon: No such image"
+    ));
+}
+
+#[test]
+fn reaper_script_should_force_remove_after_lifetime() {
+    assert_eq!(
+        reaper_script("docker", "0123456789ab", time::Duration::from_secs(600)),
+        "sleep 600.000; 'docker' rm -f -v 0123456789ab"
+    );
+    assert_eq!(
+        reaper_script(
+            "/opt/my bin/podman",
+            "0123456789ab",
+            time::Duration::from_secs(1)
+        ),
+        "sleep 1.000; '/opt/my bin/podman' rm -f -v 0123456789ab"
+    );
 }
 
 #[test]
@@ -192,5 +1469,7 @@ fn start_and_stop_container() {
     let port = "80";
     let args = &[];
     let container = start_container(image, port, args).unwrap();
+    stop_container(container.id.clone()).unwrap();
+    // stopping an already removed container is a no-op
     stop_container(container.id).unwrap();
 }
@@ -0,0 +1,88 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// RUNNING counts the containers started by this process and not removed yet.
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+/// RESERVED has the ids of the containers holding a slot, so removing a
+/// reused container, or one this process didn't start, frees no slot.
+static RESERVED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// reserves a slot for a new container, failing once `DOCKER_TESTER_MAX_CONTAINERS`
+/// containers are running, so a runaway test can't exhaust the host.
+pub(crate) fn reserve() -> Result<(), anyhow::Error> {
+    let max = match std::env::var("DOCKER_TESTER_MAX_CONTAINERS") {
+        Ok(max) => Some(parse_max_containers(&max)?),
+        Err(_) => None,
+    };
+    let running = RUNNING.fetch_add(1, Ordering::SeqCst);
+    if let Some(max) = max.filter(|max| running >= *max) {
+        RUNNING.fetch_sub(1, Ordering::SeqCst);
+        return Err(anyhow::anyhow!(
+            "{running} containers are already running, DOCKER_TESTER_MAX_CONTAINERS allows {max}"
+        ));
+    }
+    Ok(())
+}
+
+/// records that the container started with the last reserved slot holds it.
+pub(crate) fn assign(id: &str) {
+    RESERVED
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .push(id.to_string());
+}
+
+/// releases the slot of a removed container, when it holds one.
+pub(crate) fn release_container(id: &str) {
+    if take_reserved(id) {
+        release();
+    }
+}
+
+/// forgets the slot of the container, a short or full id, telling whether it held one.
+fn take_reserved(id: &str) -> bool {
+    let mut reserved = RESERVED.lock().unwrap_or_else(|err| err.into_inner());
+    match reserved
+        .iter()
+        .position(|held| held.starts_with(id) || id.starts_with(held.as_str()))
+    {
+        Some(index) => {
+            reserved.swap_remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+/// releases a reserved slot no container got.
+pub(crate) fn release() {
+    let _ = RUNNING.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |running| {
+        running.checked_sub(1)
+    });
+}
+
+fn parse_max_containers(max: &str) -> Result<usize, anyhow::Error> {
+    max.trim()
+        .parse::<usize>()
+        .map_err(|_| anyhow::anyhow!("DOCKER_TESTER_MAX_CONTAINERS must be a number, got {max:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_max_containers_should_reject_non_numbers() {
+        assert_eq!(parse_max_containers(" 20 ").unwrap(), 20);
+        assert!(parse_max_containers("many").is_err());
+    }
+
+    #[test]
+    fn take_reserved_should_only_free_a_held_slot_once() {
+        assign("feedc0ffee01");
+        assert!(take_reserved("feedc0ffee01aabbccddeeff"));
+        assert!(!take_reserved("feedc0ffee01"));
+        assert!(!take_reserved("0ddba11c0ffe"));
+    }
+}
@@ -0,0 +1,149 @@
+use serde::Deserialize;
+
+use crate::error::docker_error;
+use crate::runtime::docker_command;
+use crate::{artifact, dump, LABEL};
+
+/// ContainerInfo describes a container started by this crate, as listed by `docker ps`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ContainerInfo {
+    #[serde(alias = "ID")]
+    pub id: String,
+
+    #[serde(alias = "Image")]
+    pub image: String,
+
+    #[serde(alias = "Status")]
+    pub status: String,
+
+    #[serde(alias = "Names", deserialize_with = "split_names")]
+    pub names: Vec<String>,
+
+    #[serde(alias = "Ports")]
+    pub ports: String,
+}
+
+/// Lists the running containers started by this crate.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::list_containers;
+///
+/// for container in list_containers().expect("Failed to list containers") {
+///     println!("{} {}", container.id, container.image);
+/// }
+/// ```
+pub fn list_containers() -> Result<Vec<ContainerInfo>, anyhow::Error> {
+    let output = docker_command()
+        .arg("ps")
+        .arg("--filter")
+        .arg(format!("label={LABEL}"))
+        .arg("--format")
+        .arg("{{json .}}")
+        .output()?;
+    if !output.status.success() {
+        return Err(docker_error(output.stderr));
+    }
+    parse_ps_output(&String::from_utf8(output.stdout)?)
+}
+
+/// Force-removes the containers started by this crate that outlived their
+/// test process, e.g. one killed with SIGKILL before the drops ran, with their
+/// volumes, and returns how many it removed. The containers of this process
+/// are kept. Call it before the tests start, the containers of a test process
+/// running next to this one look orphaned too, and so do reused containers.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::cleanup_orphans;
+///
+/// let removed = cleanup_orphans().expect("Failed to clean up");
+/// println!("removed {removed} leaked containers");
+/// ```
+pub fn cleanup_orphans() -> Result<usize, anyhow::Error> {
+    let output = docker_command()
+        .arg("ps")
+        .arg("-aq")
+        .arg("--filter")
+        .arg(format!("label={LABEL}=true"))
+        .output()?;
+    if !output.status.success() {
+        return Err(docker_error(output.stderr));
+    }
+    let mut removed = 0;
+    let mut failures = Vec::new();
+    for id in String::from_utf8(output.stdout)?.split_whitespace() {
+        if dump::is_tracked(id) {
+            continue;
+        }
+        let output = docker_command()
+            .arg("rm")
+            .arg("-f")
+            .arg("-v")
+            .arg(id)
+            .output()?;
+        let result = match output.status.success() {
+            true => Ok(()),
+            false => Err(docker_error(output.stderr)),
+        };
+        artifact::record_stop(id, "remove", &result);
+        match result {
+            Ok(()) => removed += 1,
+            Err(err) => failures.push(format!("{id}: {}", err.to_string().trim())),
+        }
+    }
+    if !failures.is_empty() {
+        return Err(anyhow::anyhow!(
+            "removed {removed} containers, cannot remove {}: {}",
+            failures.len(),
+            failures.join("; ")
+        ));
+    }
+    info!("Removed {removed} orphaned containers");
+    Ok(removed)
+}
+
+fn parse_ps_output(output: &str) -> Result<Vec<ContainerInfo>, anyhow::Error> {
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+fn split_names<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let names = String::deserialize(deserializer)?;
+    Ok(names
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .map(|name| name.to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ps_output_should_read_json_lines() {
+        let output = r#"{"Command":"\"docker-entrypoint.s…\"","CreatedAt":"2022-11-28 13:55:05 +0800 CST","ID":"dfd60e4ef0c0","Image":"postgres:14-alpine","Labels":"docker-tester=true","LocalVolumes":"1","Mounts":"8f6c0e","Names":"eager_kepler,pg","Networks":"bridge","Ports":"0.0.0.0:49153->5432/tcp","RunningFor":"2 minutes ago","Size":"0B","State":"running","Status":"Up 2 minutes"}
+"#;
+        let containers = parse_ps_output(output).unwrap();
+        assert_eq!(
+            containers,
+            vec![ContainerInfo {
+                id: "dfd60e4ef0c0".to_string(),
+                image: "postgres:14-alpine".to_string(),
+                status: "Up 2 minutes".to_string(),
+                names: vec!["eager_kepler".to_string(), "pg".to_string()],
+                ports: "0.0.0.0:49153->5432/tcp".to_string(),
+            }]
+        );
+        assert!(parse_ps_output("").unwrap().is_empty());
+    }
+}
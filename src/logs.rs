@@ -0,0 +1,163 @@
+use std::io::{BufRead, BufReader, Read};
+use std::ops::Deref;
+use std::process::{Child, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::runtime::docker_command;
+use crate::Container;
+
+/// LogFollower follows the logs of a container in the background,
+/// the follower stops when it's dropped.
+pub struct LogFollower {
+    child: Child,
+}
+
+impl Drop for LogFollower {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// LogReceiver receives the log lines of a container as they're written,
+/// dropping it stops following the logs.
+pub struct LogReceiver {
+    receiver: Receiver<String>,
+    _follower: LogFollower,
+}
+
+impl Deref for LogReceiver {
+    type Target = Receiver<String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.receiver
+    }
+}
+
+impl Container {
+    /// Follows the logs of the container in the background and sends every
+    /// line of stdout and stderr to the receiver, e.g. to assert the service
+    /// logged a line shortly after the test triggered it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::start_container;
+    /// use std::time::Duration;
+    ///
+    /// let container = start_container("my-service:latest", "8080", &[])
+    ///     .expect("Failed to start the container");
+    /// let logs = container.log_channel().expect("Failed to follow the logs");
+    /// // trigger the batch
+    /// let processed = std::iter::from_fn(|| logs.recv_timeout(Duration::from_secs(5)).ok())
+    ///     .any(|line| line.contains("processed batch"));
+    /// assert!(processed);
+    /// ```
+    pub fn log_channel(&self) -> Result<LogReceiver, anyhow::Error> {
+        let (sender, receiver) = mpsc::channel();
+        let follower = follow_logs(&self.id, move |line| {
+            // the receiver is dropped together with the follower
+            let _ = sender.send(line.to_string());
+        })?;
+        Ok(LogReceiver {
+            receiver,
+            _follower: follower,
+        })
+    }
+
+    /// Emits every log line of the container as a `tracing::info!` event inside
+    /// a `container` span carrying the container id and image.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::start_container;
+    ///
+    /// let container = start_container("redis:7-alpine", "6379", &[])
+    ///     .expect("Failed to start Redis container");
+    /// let _follower = container.trace_logs().expect("Failed to follow the logs");
+    /// ```
+    #[cfg(feature = "tracing")]
+    pub fn trace_logs(&self) -> Result<LogFollower, anyhow::Error> {
+        let span = tracing::info_span!("container", id = %self.id, image = %self.image);
+        follow_logs(&self.id, move |line| {
+            let _enter = span.enter();
+            tracing::info!("{line}");
+        })
+    }
+}
+
+/// Waits until a line of the container logs contains the text, following the
+/// logs from the start, e.g. until a service logged it finished a job the test
+/// triggered. It fails once the timeout elapsed or the container exited.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::{start_container, wait_for_log};
+/// use std::time::Duration;
+///
+/// let container = start_container("my-service:latest", "8080", &[])
+///     .expect("Failed to start the container");
+/// wait_for_log(&container.id, "cache warmed up", Duration::from_secs(30))
+///     .expect("The cache never warmed up");
+/// ```
+pub fn wait_for_log(id: &str, text: &str, timeout: Duration) -> Result<(), anyhow::Error> {
+    let (sender, receiver) = mpsc::channel();
+    let text_owned = text.to_string();
+    let _follower = follow_logs(id, move |line| {
+        if line.contains(text_owned.as_str()) {
+            let _ = sender.send(());
+        }
+    })?;
+    match receiver.recv_timeout(timeout) {
+        Ok(()) => Ok(()),
+        Err(RecvTimeoutError::Timeout) => Err(anyhow::anyhow!(
+            "timed out after {timeout:?} waiting for the container[{id}] to log {text:?}"
+        )),
+        // both readers ended, so docker logs -f exited with the container
+        Err(RecvTimeoutError::Disconnected) => Err(anyhow::anyhow!(
+            "the container[{id}] logs ended without {text:?}"
+        )),
+    }
+}
+
+/// runs `docker logs -f` and calls on_line for each line of stdout and stderr.
+pub(crate) fn follow_logs<F>(id: &str, on_line: F) -> Result<LogFollower, anyhow::Error>
+where
+    F: Fn(&str) + Send + Sync + 'static,
+{
+    let mut child = docker_command()
+        .arg("logs")
+        .arg("-f")
+        .arg(id)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let on_line = Arc::new(on_line);
+    if let Some(stdout) = child.stdout.take() {
+        spawn_reader(stdout, on_line.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        spawn_reader(stderr, on_line);
+    }
+    Ok(LogFollower { child })
+}
+
+fn spawn_reader<R, F>(reader: R, on_line: Arc<F>)
+where
+    R: Read + Send + 'static,
+    F: Fn(&str) + Send + Sync + 'static,
+{
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            match line {
+                Ok(line) => on_line(&line),
+                Err(_) => break,
+            }
+        }
+    });
+}
@@ -0,0 +1,91 @@
+use crate::error::docker_error;
+use crate::runtime::docker_command;
+use crate::{NetworkMode, LABEL};
+
+/// NetworkGuard owns a user-defined docker network and removes it on drop.
+/// Containers started on it with NetworkMode::Custom reach each other by
+/// their StartOptions::name.
+#[derive(Debug)]
+pub struct NetworkGuard {
+    id: String,
+    name: String,
+}
+
+impl NetworkGuard {
+    /// the id docker gave the network.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// the name of the network.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// the NetworkMode to start the containers on this network with.
+    pub fn network_mode(&self) -> NetworkMode {
+        NetworkMode::Custom(self.name.clone())
+    }
+}
+
+impl Drop for NetworkGuard {
+    fn drop(&mut self) {
+        // the containers on the network must be removed first, the guards
+        // declared after this one are dropped before it
+        match docker_command()
+            .arg("network")
+            .arg("rm")
+            .arg(&self.name)
+            .output()
+        {
+            Ok(output) if output.status.success() => info!("Network[{}] dropped", self.name),
+            Ok(output) => warn!(
+                "Network[{}] cannot be removed: {}",
+                self.name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(err) => warn!("Network[{}] cannot be removed: {err}", self.name),
+        }
+    }
+}
+
+/// Creates a user-defined docker network, removed when the guard is dropped,
+/// e.g. so an app container reaches a Postgres container by name.
+///
+/// # Example
+///
+/// ```no_run
+/// use docker_tester::{create_network, start_container_guarded, StartOptions};
+///
+/// let network = create_network("orders-test").expect("Failed to create the network");
+/// let postgres = start_container_guarded(
+///     "postgres:14-alpine",
+///     "5432",
+///     &["-e", "POSTGRES_PASSWORD=password"],
+///     StartOptions {
+///         name: Some("orders-pg".to_string()),
+///         network_mode: network.network_mode(),
+///         ..Default::default()
+///     },
+/// )
+/// .expect("Failed to start Postgres");
+/// // another container on the network connects to orders-pg:5432
+/// ```
+pub fn create_network(name: &str) -> Result<NetworkGuard, anyhow::Error> {
+    let output = docker_command()
+        .arg("network")
+        .arg("create")
+        .arg("--label")
+        .arg(format!("{LABEL}=true"))
+        .arg(name)
+        .output()?;
+    if !output.status.success() {
+        return Err(docker_error(output.stderr));
+    }
+    let id = String::from_utf8(output.stdout)?.trim().to_string();
+    info!("Network[{name}] created");
+    Ok(NetworkGuard {
+        id,
+        name: name.to_string(),
+    })
+}
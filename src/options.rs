@@ -0,0 +1,705 @@
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::wait::{readiness_timeout, Backoff};
+use crate::{remote_docker_host, Protocol, WaitStrategy, HOST_GATEWAY};
+
+/// StartOptions customizes how start_container_with_opts runs a container.
+#[derive(Debug, Clone)]
+pub struct StartOptions {
+    /// decides when the container is ready, defaults to WaitStrategy::Running.
+    pub wait: WaitStrategy,
+    /// bounds the readiness wait. When unset, the `DOCKER_TESTER_TIMEOUT_SECS`
+    /// env var is used, and 45 seconds without it.
+    pub timeout: Option<Duration>,
+    /// stops the readiness wait after this many checks.
+    pub max_attempts: Option<u32>,
+    /// waits this long between the readiness checks, instead of one more second
+    /// per retry. With max_attempts and no timeout, the total wait is
+    /// `max_attempts * poll_interval`, e.g. 600 attempts of 200ms for 2 minutes.
+    pub poll_interval: Option<Duration>,
+    /// once the wait strategy succeeded, also waits until the container has been
+    /// running this long according to `.State.StartedAt`, for services that
+    /// need to warm up after they report ready.
+    pub min_uptime: Option<Duration>,
+    /// the command passed to the container after the image name,
+    /// overriding the `CMD` of the image.
+    pub command: Vec<String>,
+    /// checks the docker data root has at least this many free bytes before
+    /// starting the container, see check_disk_space.
+    pub min_disk_space: Option<u64>,
+    /// mounts a named volume as `(volume name, mount path)`, the volume and its
+    /// data outlive the container so expensive fixtures survive across test runs.
+    /// Use volume_exists to skip seeding it again and remove_volume to start over.
+    pub named_volume: Option<(String, String)>,
+    /// bind mounts host paths into the container (`-v`), e.g. an init SQL
+    /// directory on `/docker-entrypoint-initdb.d`.
+    pub mounts: Vec<Mount>,
+    /// adds a healthcheck at run time, for images that don't ship one,
+    /// so WaitStrategy::Healthy can be used with them.
+    pub healthcheck: Option<HealthcheckSpec>,
+    /// runs the container under this cgroup parent (`--cgroup-parent`), so all the
+    /// test containers can be limited or monitored together. The cgroup must exist.
+    pub cgroup_parent: Option<String>,
+    /// pins the container to these CPUs (`--cpuset-cpus`), e.g. `0,1` or `0-3`.
+    /// The host must have the CPUs, docker rejects the run otherwise.
+    pub cpuset_cpus: Option<String>,
+    /// pins the container to these NUMA memory nodes (`--cpuset-mems`).
+    pub cpuset_mems: Option<String>,
+    /// caps the memory of the container (`--memory`), e.g. `512m` or `2g`,
+    /// so a test database can't starve the other processes. An empty value is ignored.
+    pub memory: Option<String>,
+    /// caps how many CPUs the container may use (`--cpus`), e.g. `1.5`.
+    /// An empty value is ignored.
+    pub cpus: Option<String>,
+    /// lets the container reach servers running on the host through
+    /// `host.docker.internal` (`--add-host host.docker.internal:host-gateway`),
+    /// see host_reachable_url. The alias is added on a native Linux daemon
+    /// anyway, set it to force it elsewhere.
+    pub host_gateway: bool,
+    /// mounts a tmpfs at each path (`--tmpfs`), e.g. `/tmp` or `/run:size=64m`.
+    pub tmpfs: Vec<String>,
+    /// makes the root filesystem read-only (`--read-only`), combine it with tmpfs
+    /// and volumes for the directories the service is expected to write to.
+    pub read_only_rootfs: bool,
+    /// gives the container GPUs (`--gpus`), e.g. `all` or `device=0`. The host
+    /// needs the NVIDIA container toolkit, docker run fails without it.
+    pub gpus: Option<String>,
+    /// the platform to pull and run, as `os/arch` or `os/arch/variant`,
+    /// e.g. `linux/amd64` or `linux/arm/v7`.
+    pub platform: Option<String>,
+    /// pulls a missing image with `docker pull --quiet`, capturing the output
+    /// for the error instead of showing the progress. Defaults to true unless
+    /// stdout is a terminal, so CI logs stay clean.
+    pub pull_quiet: bool,
+    /// skips the explicit `docker pull` of a missing image, `docker run`
+    /// pulls it itself then, e.g. to save the inspect call with a warm cache.
+    pub skip_pull: bool,
+    /// force-removes the container once it ran this long, even when the guard is
+    /// forgotten or the test process hangs or gets killed. It's a last-resort reaper
+    /// for long dev sessions, disabled by default; stopping the container afterwards fails.
+    pub max_lifetime: Option<Duration>,
+    /// runs the container as the uid:gid of the test process (`--user`) when
+    /// there are bind mounts, in mounts or among the args, so the files it writes there are
+    /// owned by the test user instead of root. Needs the `id` command.
+    pub run_as_host_user: bool,
+    /// the protocol of the ports to discover, Protocol::Udp for e.g. a StatsD
+    /// receiver. A port spec like `5353/udp` has its own.
+    pub protocol: Protocol,
+    /// reuses a container started earlier with the same image, args and options,
+    /// possibly by another test process, instead of starting another one.
+    /// Don't stop a reused container, other tests may still use it.
+    pub reuse: bool,
+    /// removes a matching container that isn't running anymore, e.g. one that
+    /// crashed, and starts a fresh one instead of reusing it. When false, it's
+    /// started again and waited for. Defaults to true.
+    pub reuse_only_if_running: bool,
+    /// the host address the container ports are published on, e.g. `0.0.0.0`
+    /// to reach them from other machines. Defaults to `127.0.0.1`, so a test
+    /// database isn't exposed on every interface, or to `0.0.0.0` with a remote
+    /// docker daemon set through `DOCKER_HOST`, whose loopback can't be reached.
+    pub bind_ip: Option<String>,
+    /// the network of the container (`--network`), defaults to NetworkMode::Bridge.
+    pub network_mode: NetworkMode,
+    /// extra labels (`--label`), e.g. to find the containers of a suite with
+    /// `docker ps --filter label=...`. They're part of the reuse key.
+    pub labels: BTreeMap<String, String>,
+    /// the timezone of the container (`-e TZ=...`), e.g. `UTC` or
+    /// `America/New_York`. Defaults to the image's.
+    pub timezone: Option<String>,
+    /// the name of the container (`--name`) instead of a random one, e.g. to
+    /// tell which test leaked it. Starting a second container with the name fails.
+    pub name: Option<String>,
+    /// the environment variables of the container, passed as `-e KEY=VALUE`
+    /// before the raw args, so an `-e` in the args can still override one.
+    /// Sorted by key, so the reuse key doesn't change between runs.
+    pub env: BTreeMap<String, String>,
+}
+
+/// NetworkMode is the docker network a container joins.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum NetworkMode {
+    /// the default bridge network, with the ports mapped to the host.
+    #[default]
+    Bridge,
+    /// shares the network namespace of the host, Linux only: on Docker Desktop
+    /// the "host" is the VM. Nothing is mapped, Container::port is the
+    /// requested container port.
+    Host,
+    /// no network at all, the container port can't be reached.
+    None,
+    /// a user-defined network, by name.
+    Custom(String),
+}
+
+impl NetworkMode {
+    /// tells whether the container ports are mapped to host ports.
+    pub(crate) fn maps_ports(&self) -> bool {
+        !matches!(self, NetworkMode::Host | NetworkMode::None)
+    }
+}
+
+impl Default for StartOptions {
+    fn default() -> Self {
+        Self {
+            wait: WaitStrategy::default(),
+            timeout: None,
+            max_attempts: None,
+            poll_interval: None,
+            min_uptime: None,
+            command: Vec::new(),
+            min_disk_space: None,
+            named_volume: None,
+            mounts: Vec::new(),
+            healthcheck: None,
+            cgroup_parent: None,
+            cpuset_cpus: None,
+            cpuset_mems: None,
+            memory: None,
+            cpus: None,
+            host_gateway: false,
+            tmpfs: Vec::new(),
+            read_only_rootfs: false,
+            platform: None,
+            gpus: None,
+            pull_quiet: !std::io::stdout().is_terminal(),
+            skip_pull: false,
+            max_lifetime: None,
+            run_as_host_user: false,
+            protocol: Protocol::default(),
+            reuse: false,
+            reuse_only_if_running: true,
+            bind_ip: None,
+            network_mode: NetworkMode::default(),
+            labels: BTreeMap::new(),
+            timezone: None,
+            name: None,
+            env: BTreeMap::new(),
+        }
+    }
+}
+
+impl StartOptions {
+    /// checks the options are coherent before touching docker.
+    pub(crate) fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.max_attempts == Some(0) {
+            return Err(anyhow::anyhow!("max_attempts must be at least 1"));
+        }
+        if let Some(platform) = &self.platform {
+            let parts: Vec<&str> = platform.split('/').collect();
+            if !(2..=3).contains(&parts.len()) || parts.iter().any(|part| part.is_empty()) {
+                return Err(anyhow::anyhow!(
+                    "invalid platform {platform:?}, expected os/arch or os/arch/variant"
+                ));
+            }
+        }
+        if let Some((name, path)) = &self.named_volume {
+            if name.is_empty() || !path.starts_with('/') {
+                return Err(anyhow::anyhow!(
+                    "invalid named volume {name:?}, it must be mounted on an absolute path"
+                ));
+            }
+        }
+        for tmpfs in &self.tmpfs {
+            if !tmpfs.starts_with('/') {
+                return Err(anyhow::anyhow!(
+                    "invalid tmpfs {tmpfs:?}, it must be an absolute path"
+                ));
+            }
+        }
+        for mount in &self.mounts {
+            mount.validate()?;
+        }
+        if let Some(name) = &self.name {
+            // docker only takes [a-zA-Z0-9][a-zA-Z0-9_.-]*
+            let valid = name.starts_with(|c: char| c.is_ascii_alphanumeric())
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+            if !valid {
+                return Err(anyhow::anyhow!(
+                    "invalid container name {name:?}, expected [a-zA-Z0-9][a-zA-Z0-9_.-]*"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// resolves the readiness wait bound, an explicit timeout wins over
+    /// `max_attempts * poll_interval`, which wins over readiness_timeout.
+    pub(crate) fn wait_timeout(&self) -> Result<Duration, anyhow::Error> {
+        match (self.timeout, self.max_attempts, self.poll_interval) {
+            (None, Some(max_attempts), Some(poll_interval)) => Ok(poll_interval * max_attempts),
+            (timeout, ..) => readiness_timeout(timeout),
+        }
+    }
+
+    /// the Backoff pacing the readiness wait.
+    pub(crate) fn backoff(&self) -> Result<Backoff, anyhow::Error> {
+        Ok(Backoff::new(self.wait_timeout()?)
+            .max_attempts(self.max_attempts)
+            .interval(self.poll_interval))
+    }
+
+    /// publishes the container ports on bind_ip (`-p ip::port/protocol`),
+    /// docker picks free host ports.
+    pub(crate) fn publish_args(&self, ports: &[(u16, Protocol)]) -> Vec<String> {
+        if !self.network_mode.maps_ports() {
+            return Vec::new();
+        }
+        let ip = match &self.bind_ip {
+            Some(ip) => ip.clone(),
+            None if remote_docker_host().is_some() => "0.0.0.0".to_string(),
+            None => "127.0.0.1".to_string(),
+        };
+        // an IPv6 address would be ambiguous next to the port separators
+        let ip = match ip.contains(':') {
+            true => format!("[{ip}]"),
+            false => ip,
+        };
+        ports
+            .iter()
+            .flat_map(|(port, protocol)| ["-p".to_string(), format!("{ip}::{port}/{protocol}")])
+            .collect()
+    }
+
+    /// translates the options into `docker run` flags.
+    pub(crate) fn run_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some((name, path)) = &self.named_volume {
+            args.push("-v".to_string());
+            args.push(format!("{name}:{path}"));
+        }
+        for mount in &self.mounts {
+            args.push("-v".to_string());
+            args.push(mount.run_arg());
+        }
+        if let Some(healthcheck) = &self.healthcheck {
+            args.extend(healthcheck.run_args());
+        }
+        if let Some(cgroup_parent) = &self.cgroup_parent {
+            args.push(format!("--cgroup-parent={cgroup_parent}"));
+        }
+        if let Some(cpuset_cpus) = &self.cpuset_cpus {
+            args.push(format!("--cpuset-cpus={cpuset_cpus}"));
+        }
+        if let Some(cpuset_mems) = &self.cpuset_mems {
+            args.push(format!("--cpuset-mems={cpuset_mems}"));
+        }
+        // docker rejects malformed limits itself
+        if let Some(memory) = self.memory.as_deref().filter(|m| !m.trim().is_empty()) {
+            args.push(format!("--memory={}", memory.trim()));
+        }
+        if let Some(cpus) = self.cpus.as_deref().filter(|c| !c.trim().is_empty()) {
+            args.push(format!("--cpus={}", cpus.trim()));
+        }
+        if self.host_gateway {
+            args.push("--add-host".to_string());
+            args.push(format!("{HOST_GATEWAY}:host-gateway"));
+        }
+        for tmpfs in &self.tmpfs {
+            args.push(format!("--tmpfs={tmpfs}"));
+        }
+        if self.read_only_rootfs {
+            args.push("--read-only".to_string());
+        }
+        if let Some(platform) = &self.platform {
+            args.push(format!("--platform={platform}"));
+        }
+        if let Some(gpus) = &self.gpus {
+            args.push(format!("--gpus={gpus}"));
+        }
+        match &self.network_mode {
+            NetworkMode::Bridge => {}
+            NetworkMode::Host => args.push("--network=host".to_string()),
+            NetworkMode::None => args.push("--network=none".to_string()),
+            NetworkMode::Custom(network) => args.push(format!("--network={network}")),
+        }
+        for (key, value) in &self.labels {
+            args.push(format!("--label={key}={value}"));
+        }
+        for (key, value) in &self.env {
+            args.push("-e".to_string());
+            args.push(format!("{key}={value}"));
+        }
+        if let Some(timezone) = &self.timezone {
+            args.push("-e".to_string());
+            args.push(format!("TZ={timezone}"));
+        }
+        if let Some(name) = &self.name {
+            args.push(format!("--name={name}"));
+        }
+        args
+    }
+}
+
+/// Mount bind mounts a host path into the container.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Mount {
+    /// the host file or directory, a relative path is resolved against the
+    /// current directory, e.g. the crate root under `cargo test`.
+    pub source: PathBuf,
+    /// the absolute path inside the container.
+    pub target: String,
+    pub read_only: bool,
+}
+
+impl Mount {
+    /// mounts source on target, writable.
+    pub fn new(source: impl Into<PathBuf>, target: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            target: target.into(),
+            read_only: false,
+        }
+    }
+
+    /// mounts it read-only (`:ro`).
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        if !self.target.starts_with('/') {
+            return Err(anyhow::anyhow!(
+                "invalid mount target {:?}, it must be an absolute path",
+                self.target
+            ));
+        }
+        // docker would create a missing source as an empty root-owned directory
+        std::fs::canonicalize(&self.source)
+            .map_err(|err| anyhow::anyhow!("cannot mount {}: {err}", self.source.display()))?;
+        Ok(())
+    }
+
+    /// the `src:target[:ro]` value of `-v`, with the canonical source path.
+    fn run_arg(&self) -> String {
+        let source = std::fs::canonicalize(&self.source).unwrap_or_else(|_| self.source.clone());
+        let mut arg = format!("{}:{}", source.display(), self.target);
+        if self.read_only {
+            arg.push_str(":ro");
+        }
+        arg
+    }
+}
+
+/// HealthcheckSpec is a healthcheck passed to `docker run`, like the
+/// `healthcheck` section of a docker-compose file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthcheckSpec {
+    /// the command run by the shell inside the container, healthy when it exits with 0.
+    pub cmd: String,
+    pub interval: Duration,
+    pub retries: u32,
+    /// the time a single check may take, the docker default is used when unset.
+    pub timeout: Option<Duration>,
+}
+
+impl HealthcheckSpec {
+    /// creates a HealthcheckSpec running cmd every second, up to 3 failures in a row.
+    pub fn new(cmd: impl Into<String>) -> Self {
+        Self {
+            cmd: cmd.into(),
+            interval: Duration::from_secs(1),
+            retries: 3,
+            timeout: None,
+        }
+    }
+
+    fn run_args(&self) -> Vec<String> {
+        let mut args = vec![
+            format!("--health-cmd={}", self.cmd),
+            format!("--health-interval={}ms", self.interval.as_millis()),
+            format!("--health-retries={}", self.retries),
+        ];
+        if let Some(timeout) = self.timeout {
+            args.push(format!("--health-timeout={}ms", timeout.as_millis()));
+        }
+        args
+    }
+}
+
+/// RemovalMode decides whether a dropped tester, ContainerGuard or TestEnv
+/// removes its containers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RemovalMode {
+    /// always removes the container.
+    #[default]
+    Always,
+    /// removes the container when the test passed, keeping it for inspection
+    /// when the test is panicking.
+    OnSuccess,
+    /// removes the container only when the test is panicking.
+    OnFailure,
+    /// never removes the container. Kept containers carry the `docker-tester`
+    /// label, find them with list_containers and remove them in a batch.
+    Never,
+}
+
+impl RemovalMode {
+    /// tells whether the container must be removed, given the current thread's state.
+    ///
+    /// When `DOCKER_TESTER_KEEP_ON_FAILURE=1` a panicking test keeps its
+    /// container whatever the mode, like RemovalMode::OnSuccess.
+    pub fn should_remove(&self) -> bool {
+        self.should_remove_when(std::thread::panicking(), keep_on_failure())
+    }
+
+    fn should_remove_when(&self, panicking: bool, keep_on_failure: bool) -> bool {
+        if panicking && keep_on_failure {
+            return false;
+        }
+        match self {
+            RemovalMode::Always => true,
+            RemovalMode::OnSuccess => !panicking,
+            RemovalMode::OnFailure => panicking,
+            RemovalMode::Never => false,
+        }
+    }
+}
+
+/// tells whether `DOCKER_TESTER_KEEP_ON_FAILURE` asks to keep the containers of failed tests.
+fn keep_on_failure() -> bool {
+    matches!(
+        std::env::var("DOCKER_TESTER_KEEP_ON_FAILURE").as_deref(),
+        Ok("1" | "true")
+    )
+}
+
+/// StopOptions customizes how stop_container_with_opts tears a container down.
+#[derive(Debug, Clone)]
+pub struct StopOptions {
+    /// removes the anonymous volumes of the container, defaults to true.
+    ///
+    /// Turn it off to keep the data directory around after a failed test,
+    /// it can then be mounted into another container for inspection.
+    pub remove_volumes: bool,
+    /// removes the container after stopping it, defaults to true.
+    ///
+    /// Turn it off to `docker start` the same container with its data in a
+    /// later phase of the test, see stop_only.
+    pub remove: bool,
+}
+
+impl Default for StopOptions {
+    fn default() -> Self {
+        Self {
+            remove_volumes: true,
+            remove: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_args_should_translate_the_options() {
+        let opts = StartOptions {
+            named_volume: Some(("fixtures".to_string(), "/data".to_string())),
+            healthcheck: Some(HealthcheckSpec {
+                timeout: Some(Duration::from_secs(2)),
+                ..HealthcheckSpec::new("pg_isready")
+            }),
+            cgroup_parent: Some("docker-tester.slice".to_string()),
+            cpuset_cpus: Some("0,1".to_string()),
+            memory: Some("512m".to_string()),
+            cpus: Some("1.5".to_string()),
+            tmpfs: vec!["/tmp".to_string()],
+            read_only_rootfs: true,
+            gpus: Some("all".to_string()),
+            name: Some("orders-pg".to_string()),
+            env: BTreeMap::from([
+                ("POSTGRES_USER".to_string(), "orders".to_string()),
+                ("PGDATA".to_string(), "/tmp/pgdata".to_string()),
+            ]),
+            timezone: Some("America/New_York".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            opts.run_args(),
+            vec![
+                "-v",
+                "fixtures:/data",
+                "--health-cmd=pg_isready",
+                "--health-interval=1000ms",
+                "--health-retries=3",
+                "--health-timeout=2000ms",
+                "--cgroup-parent=docker-tester.slice",
+                "--cpuset-cpus=0,1",
+                "--memory=512m",
+                "--cpus=1.5",
+                "--tmpfs=/tmp",
+                "--read-only",
+                "--gpus=all",
+                "-e",
+                "PGDATA=/tmp/pgdata",
+                "-e",
+                "POSTGRES_USER=orders",
+                "-e",
+                "TZ=America/New_York",
+                "--name=orders-pg",
+            ]
+        );
+        assert!(StartOptions::default().run_args().is_empty());
+        let opts = StartOptions {
+            memory: Some(String::new()),
+            cpus: Some(" ".to_string()),
+            ..Default::default()
+        };
+        assert!(opts.run_args().is_empty());
+        let opts = StartOptions {
+            network_mode: NetworkMode::Host,
+            ..Default::default()
+        };
+        assert_eq!(opts.run_args(), vec!["--network=host"]);
+        let opts = StartOptions {
+            network_mode: NetworkMode::Custom("app".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(opts.run_args(), vec!["--network=app"]);
+        let opts = StartOptions {
+            labels: BTreeMap::from([("suite".to_string(), "orders".to_string())]),
+            ..Default::default()
+        };
+        assert_eq!(opts.run_args(), vec!["--label=suite=orders"]);
+    }
+
+    #[test]
+    fn publish_args_should_bind_the_ports_on_loopback() {
+        if remote_docker_host().is_some() {
+            return;
+        }
+        let opts = StartOptions::default();
+        assert_eq!(
+            opts.publish_args(&[(5432, Protocol::Tcp), (8080, Protocol::Tcp)]),
+            ["-p", "127.0.0.1::5432/tcp", "-p", "127.0.0.1::8080/tcp"]
+        );
+        let opts = StartOptions {
+            bind_ip: Some("::".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            opts.publish_args(&[(53, Protocol::Tcp), (53, Protocol::Udp)]),
+            ["-p", "[::]::53/tcp", "-p", "[::]::53/udp"]
+        );
+        let opts = StartOptions {
+            network_mode: NetworkMode::Host,
+            ..Default::default()
+        };
+        assert!(opts.publish_args(&[(5432, Protocol::Tcp)]).is_empty());
+    }
+
+    #[test]
+    fn validate_should_check_the_platform() {
+        let platform = |platform: &str| StartOptions {
+            platform: Some(platform.to_string()),
+            ..Default::default()
+        };
+        assert!(platform("linux/amd64").validate().is_ok());
+        assert!(platform("linux/arm/v7").validate().is_ok());
+        assert!(platform("linux").validate().is_err());
+        assert!(platform("linux//v7").validate().is_err());
+        assert!(platform("linux/arm/v7/extra").validate().is_err());
+    }
+
+    #[test]
+    fn validate_should_check_the_name() {
+        let name = |name: &str| StartOptions {
+            name: Some(name.to_string()),
+            ..Default::default()
+        };
+        assert!(name("orders-test_pg.1").validate().is_ok());
+        assert!(name("-pg").validate().is_err());
+        assert!(name("my pg").validate().is_err());
+        assert!(name("").validate().is_err());
+    }
+
+    #[test]
+    fn validate_should_check_the_mount_paths() {
+        let opts = StartOptions {
+            named_volume: Some(("fixtures".to_string(), "/data".to_string())),
+            tmpfs: vec!["/run:size=64m".to_string()],
+            ..Default::default()
+        };
+        assert!(opts.validate().is_ok());
+        let opts = StartOptions {
+            named_volume: Some(("fixtures".to_string(), "data".to_string())),
+            ..Default::default()
+        };
+        assert!(opts.validate().is_err());
+        let opts = StartOptions {
+            tmpfs: vec!["tmp".to_string()],
+            ..Default::default()
+        };
+        assert!(opts.validate().is_err());
+        for mount in [
+            Mount::new("./missing-fixtures", "/fixtures"),
+            Mount::new("./migrations", "fixtures"),
+        ] {
+            let opts = StartOptions {
+                mounts: vec![mount],
+                ..Default::default()
+            };
+            assert!(opts.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn run_args_should_canonicalize_the_mount_source() {
+        let opts = StartOptions {
+            mounts: vec![Mount::new("./migrations", "/docker-entrypoint-initdb.d").read_only()],
+            ..Default::default()
+        };
+        assert!(opts.validate().is_ok());
+        let source = std::fs::canonicalize("./migrations").unwrap();
+        assert_eq!(
+            opts.run_args(),
+            [
+                "-v".to_string(),
+                format!("{}:/docker-entrypoint-initdb.d:ro", source.display())
+            ]
+        );
+    }
+
+    #[test]
+    fn wait_timeout_should_multiply_attempts_and_interval() {
+        let opts = StartOptions {
+            max_attempts: Some(600),
+            poll_interval: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+        assert_eq!(opts.wait_timeout().unwrap(), Duration::from_secs(120));
+        let opts = StartOptions {
+            timeout: Some(Duration::from_secs(10)),
+            ..opts
+        };
+        assert_eq!(opts.wait_timeout().unwrap(), Duration::from_secs(10));
+        let opts = StartOptions {
+            max_attempts: Some(0),
+            ..Default::default()
+        };
+        assert!(opts.validate().is_err());
+    }
+
+    #[test]
+    fn removal_mode_should_follow_the_test_outcome() {
+        assert!(RemovalMode::Always.should_remove());
+        assert!(RemovalMode::OnSuccess.should_remove());
+        assert!(!RemovalMode::OnFailure.should_remove());
+        assert!(!RemovalMode::Never.should_remove());
+    }
+
+    #[test]
+    fn keep_on_failure_should_keep_the_container_of_a_panicking_test() {
+        assert!(!RemovalMode::Always.should_remove_when(true, true));
+        assert!(!RemovalMode::OnFailure.should_remove_when(true, true));
+        assert!(RemovalMode::Always.should_remove_when(false, true));
+        assert!(RemovalMode::Always.should_remove_when(true, false));
+        assert!(!RemovalMode::Never.should_remove_when(false, true));
+    }
+}
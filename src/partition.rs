@@ -0,0 +1,112 @@
+use crate::runtime::docker_command;
+use crate::Container;
+
+impl Container {
+    /// Drops every packet sent to the service port inside the container with
+    /// an iptables rule, so it looks unreachable while the process keeps
+    /// running, e.g. to test client timeouts under a network partition.
+    /// The image needs iptables, and the container `--cap-add=NET_ADMIN`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::start_container;
+    ///
+    /// let container = start_container(
+    ///     "my-postgres-with-iptables:latest",
+    ///     "5432",
+    ///     &["--cap-add=NET_ADMIN", "-e", "POSTGRES_PASSWORD=password"],
+    /// )
+    /// .expect("Failed to start Postgres container");
+    /// container.block_port().expect("Failed to block the port");
+    /// // the client times out here
+    /// container.unblock_port().expect("Failed to unblock the port");
+    /// ```
+    pub fn block_port(&self) -> Result<(), anyhow::Error> {
+        self.iptables("-I")
+    }
+
+    /// removes the rule added by block_port.
+    pub fn unblock_port(&self) -> Result<(), anyhow::Error> {
+        self.iptables("-D")
+    }
+
+    fn iptables(&self, action: &str) -> Result<(), anyhow::Error> {
+        let container_port = self
+            .ports
+            .iter()
+            .find(|(_, host_port)| **host_port == self.port)
+            .map(|(container_port, _)| *container_port)
+            .unwrap_or(self.port);
+        // the rules need root, whatever user the image runs as
+        let output = docker_command()
+            .arg("exec")
+            .arg("--user=0")
+            .arg(&self.id)
+            .args(iptables_args(
+                action,
+                container_port,
+                self.protocol.as_str(),
+            ))
+            .output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(match iptables_hint(&stderr) {
+                Some(hint) => anyhow::anyhow!("{hint}: {}", stderr.trim()),
+                None => anyhow::anyhow!("iptables failed: {}", stderr.trim()),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn iptables_args(action: &str, port: u16, protocol: &str) -> Vec<String> {
+    [
+        "iptables",
+        action,
+        "INPUT",
+        "-p",
+        protocol,
+        "--dport",
+        &port.to_string(),
+        "-j",
+        "DROP",
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect()
+}
+
+/// explains the usual reasons iptables can't run in the container.
+fn iptables_hint(stderr: &str) -> Option<&'static str> {
+    if stderr.contains("executable file not found") || stderr.contains("not found in $PATH") {
+        Some("the image has no iptables")
+    } else if stderr.contains("Permission denied") || stderr.contains("Operation not permitted") {
+        Some("iptables needs the container started with --cap-add=NET_ADMIN")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iptables_args_should_drop_the_port() {
+        assert_eq!(
+            iptables_args("-I", 5432, "tcp"),
+            ["iptables", "-I", "INPUT", "-p", "tcp", "--dport", "5432", "-j", "DROP"]
+        );
+        assert_eq!(
+            iptables_hint("iptables v1.8.9 (nf_tables): Could not fetch rule set generation id: Permission denied (you must be root)"),
+            Some("iptables needs the container started with --cap-add=NET_ADMIN")
+        );
+        assert_eq!(
+            iptables_hint(
+                r#"OCI runtime exec failed: exec failed: unable to start container process: exec: "iptables": executable file not found in $PATH"#
+            ),
+            Some("the image has no iptables")
+        );
+    }
+}
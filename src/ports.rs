@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::inspect::inspect;
+use crate::{Container, NetworkSettings};
+
+/// Protocol is the transport protocol of a port.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Protocol {
+    #[default]
+    Tcp,
+    Udp,
+}
+
+impl Protocol {
+    /// the protocol as docker names it in port keys like `5432/tcp`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Udp => "udp",
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// PortMapping is a container port published on the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortMapping {
+    pub container_port: u16,
+    pub host_ip: String,
+    pub host_port: u16,
+    pub protocol: Protocol,
+}
+
+/// parses a port spec like `5432` or `5353/udp`, a bare port has the default protocol.
+pub(crate) fn parse_port_spec(
+    spec: &str,
+    default: Protocol,
+) -> Result<(u16, Protocol), anyhow::Error> {
+    let invalid = || anyhow::anyhow!("invalid container port {spec:?}, expected 5432 or 5353/udp");
+    let (port, protocol) = match spec.split_once('/') {
+        Some((port, "tcp")) => (port, Protocol::Tcp),
+        Some((port, "udp")) => (port, Protocol::Udp),
+        Some(_) => return Err(invalid()),
+        None => (spec, default),
+    };
+    Ok((port.parse::<u16>().map_err(|_| invalid())?, protocol))
+}
+
+impl Container {
+    /// Gets the host port a requested container port is mapped to,
+    /// see start_container_multi.
+    pub fn host_port(&self, container_port: u16) -> Option<u16> {
+        self.ports.get(&container_port).copied()
+    }
+
+    /// Gets the host port a requested container port is mapped to for the
+    /// protocol, e.g. the udp one of a DNS server started with `["53", "53/udp"]`.
+    pub fn host_port_for(&self, container_port: u16, protocol: Protocol) -> Option<u16> {
+        self.ports_by_protocol
+            .get(&(container_port, protocol))
+            .copied()
+    }
+
+    /// Gets every published port of the container from `.NetworkSettings.Ports`,
+    /// including the udp ones, sorted by container port and protocol.
+    pub fn port_mappings(&self) -> Result<Vec<PortMapping>, anyhow::Error> {
+        parse_port_mappings(&inspect(&self.id, "{{json .NetworkSettings.Ports}}")?)
+    }
+}
+
+/// parses the `.NetworkSettings.Ports` json, keyed like `5432/tcp`.
+fn parse_port_mappings(output: &str) -> Result<Vec<PortMapping>, anyhow::Error> {
+    let ports: Option<HashMap<String, Option<Vec<NetworkSettings>>>> =
+        serde_json::from_str(output)?;
+    let mut mappings = Vec::new();
+    for (key, bindings) in ports.unwrap_or_default() {
+        let (container_port, protocol) = key.split_once('/').unwrap_or((&key, "tcp"));
+        let protocol = match protocol {
+            "tcp" => Protocol::Tcp,
+            "udp" => Protocol::Udp,
+            // e.g. sctp, which can't be reached through this crate
+            _ => continue,
+        };
+        let container_port = container_port.parse::<u16>()?;
+        for binding in bindings.unwrap_or_default() {
+            mappings.push(PortMapping {
+                container_port,
+                host_ip: binding.host_ip,
+                host_port: binding.host_port.parse::<u16>()?,
+                protocol,
+            });
+        }
+    }
+    mappings.sort_by(|a, b| {
+        (a.container_port, a.protocol, &a.host_ip).cmp(&(b.container_port, b.protocol, &b.host_ip))
+    });
+    Ok(mappings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_port_should_look_up_the_container_port() {
+        let container = Container {
+            id: "0123456789ab".to_string(),
+            image: "my-service:latest".to_string(),
+            host: "0.0.0.0".to_string(),
+            port: 49153,
+            protocol: Protocol::Tcp,
+            ports: HashMap::from([(8080, 49153), (9090, 49154)]),
+            ports_by_protocol: HashMap::from([
+                ((8080, Protocol::Tcp), 49153),
+                ((9090, Protocol::Tcp), 49154),
+                ((9090, Protocol::Udp), 49155),
+            ]),
+            name: None,
+        };
+        assert_eq!(container.host_port(9090), Some(49154));
+        assert_eq!(container.host_port(9187), None);
+        assert_eq!(container.host_port_for(9090, Protocol::Udp), Some(49155));
+        assert_eq!(container.host_port_for(8080, Protocol::Udp), None);
+    }
+
+    #[test]
+    fn parse_port_spec_should_read_the_protocol() {
+        assert_eq!(
+            parse_port_spec("5432", Protocol::Tcp).unwrap(),
+            (5432, Protocol::Tcp)
+        );
+        assert_eq!(
+            parse_port_spec("8125", Protocol::Udp).unwrap(),
+            (8125, Protocol::Udp)
+        );
+        assert_eq!(
+            parse_port_spec("5353/udp", Protocol::Tcp).unwrap(),
+            (5353, Protocol::Udp)
+        );
+        assert!(parse_port_spec("5353/sctp", Protocol::Tcp).is_err());
+        assert!(parse_port_spec("http", Protocol::Tcp).is_err());
+    }
+
+    #[test]
+    fn parse_port_mappings_should_keep_the_protocol() {
+        let output = r#"{
+            "8125/udp": [{"HostIp": "0.0.0.0", "HostPort": "49160"}],
+            "5432/tcp": [{"HostIp": "0.0.0.0", "HostPort": "49153"}, {"HostIp": "::", "HostPort": "49153"}],
+            "9187/tcp": null
+        }"#;
+        let mappings = parse_port_mappings(output).unwrap();
+        assert_eq!(
+            mappings,
+            [
+                PortMapping {
+                    container_port: 5432,
+                    host_ip: "0.0.0.0".to_string(),
+                    host_port: 49153,
+                    protocol: Protocol::Tcp,
+                },
+                PortMapping {
+                    container_port: 5432,
+                    host_ip: "::".to_string(),
+                    host_port: 49153,
+                    protocol: Protocol::Tcp,
+                },
+                PortMapping {
+                    container_port: 8125,
+                    host_ip: "0.0.0.0".to_string(),
+                    host_port: 49160,
+                    protocol: Protocol::Udp,
+                },
+            ]
+        );
+        assert!(parse_port_mappings("{}").unwrap().is_empty());
+    }
+}
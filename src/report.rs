@@ -0,0 +1,34 @@
+use std::time::{Duration, Instant};
+
+/// StartReport tells how long each phase of start_container_reported took.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StartReport {
+    /// whether the image was downloaded from the registry, false when the local
+    /// cache had it. Read from the `Status:` line of `docker pull` when
+    /// StartOptions::pull_quiet is off, `--quiet` hides it.
+    pub pulled: bool,
+    pub pull_duration: Duration,
+    /// the time `docker run` took to create and start the container.
+    pub run_duration: Duration,
+    /// the time spent waiting for the container to be ready.
+    pub wait_duration: Duration,
+    /// the number of readiness checks made.
+    pub attempts: u32,
+    /// the last status docker reported for the container.
+    pub final_status: String,
+    /// every readiness check made, in order, e.g. to see how a flaky
+    /// container transitioned before it became ready.
+    pub samples: Vec<ReadinessSample>,
+}
+
+/// ReadinessSample is what a readiness check of the container saw.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadinessSample {
+    pub at: Instant,
+    /// the status docker reported, e.g. `created` or `running`.
+    pub status: String,
+    /// the healthcheck status, None when the container has no healthcheck.
+    pub health: Option<String>,
+    /// why the container wasn't ready, None for the check it became ready at.
+    pub error: Option<String>,
+}
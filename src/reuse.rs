@@ -0,0 +1,111 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::error::docker_error;
+use crate::runtime::docker_command;
+use crate::{StartOptions, LABEL};
+
+/// the label carrying the reuse key of a container started with StartOptions::reuse.
+pub(crate) fn reuse_label() -> String {
+    format!("{LABEL}.reuse")
+}
+
+/// hashes everything `docker run` gets, so only identically started containers are reused.
+pub(crate) fn reuse_key(image: &str, args: &[&str], opts: &StartOptions) -> String {
+    let mut hasher = DefaultHasher::new();
+    image.hash(&mut hasher);
+    args.hash(&mut hasher);
+    opts.run_args().hash(&mut hasher);
+    opts.command.hash(&mut hasher);
+    opts.bind_ip.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// finds a container started with the reuse key.
+///
+/// A container that isn't running, e.g. one that crashed, is removed when
+/// only_if_running is set, otherwise it's started again and waited for like a new one.
+pub(crate) fn find_reusable(
+    key: &str,
+    only_if_running: bool,
+) -> Result<Option<String>, anyhow::Error> {
+    let output = docker_command()
+        .arg("ps")
+        .arg("-a")
+        .arg("--filter")
+        .arg(format!("label={}={key}", reuse_label()))
+        .arg("--format")
+        .arg("{{.ID}} {{.State}}")
+        .output()?;
+    if !output.status.success() {
+        return Err(docker_error(output.stderr));
+    }
+    for (id, state) in parse_candidates(&String::from_utf8(output.stdout)?) {
+        if state == "running" {
+            debug!("Container[{id}] reused");
+            return Ok(Some(id));
+        }
+        if only_if_running {
+            docker(&["rm", "-f", "-v", &id])?;
+            debug!("Container[{id}] state {state}, removed instead of reused");
+            continue;
+        }
+        docker(&["start", &id])?;
+        debug!("Container[{id}] state {state}, restarted to be reused");
+        return Ok(Some(id));
+    }
+    Ok(None)
+}
+
+fn docker(args: &[&str]) -> Result<(), anyhow::Error> {
+    let output = docker_command().args(args).output()?;
+    if !output.status.success() {
+        return Err(docker_error(output.stderr));
+    }
+    Ok(())
+}
+
+/// parses `{{.ID}} {{.State}}` lines, running containers first.
+fn parse_candidates(output: &str) -> Vec<(String, String)> {
+    let mut candidates: Vec<(String, String)> = output
+        .lines()
+        .filter_map(|line| {
+            let (id, state) = line.trim().split_once(' ')?;
+            Some((id.to_string(), state.to_string()))
+        })
+        .collect();
+    candidates.sort_by_key(|(_, state)| state != "running");
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuse_key_should_change_with_the_configuration() {
+        let opts = StartOptions::default();
+        let key = reuse_key("redis:7-alpine", &[], &opts);
+        assert_eq!(key, reuse_key("redis:7-alpine", &[], &opts));
+        assert_ne!(key, reuse_key("redis:6-alpine", &[], &opts));
+        assert_ne!(key, reuse_key("redis:7-alpine", &["-e", "A=1"], &opts));
+        let opts = StartOptions {
+            read_only_rootfs: true,
+            ..Default::default()
+        };
+        assert_ne!(key, reuse_key("redis:7-alpine", &[], &opts));
+    }
+
+    #[test]
+    fn parse_candidates_should_put_running_containers_first() {
+        let output = "0123456789ab exited\nba9876543210 running\n";
+        assert_eq!(
+            parse_candidates(output),
+            [
+                ("ba9876543210".to_string(), "running".to_string()),
+                ("0123456789ab".to_string(), "exited".to_string()),
+            ]
+        );
+        assert!(parse_candidates("").is_empty());
+    }
+}
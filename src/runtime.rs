@@ -0,0 +1,21 @@
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// RUNTIME_ENV overrides the docker binary, e.g. `podman` or `/opt/bin/docker`.
+const RUNTIME_ENV: &str = "DOCKER_TESTER_BIN";
+
+/// gets the docker binary, `DOCKER_TESTER_BIN` when it's set, read once.
+pub(crate) fn docker_binary() -> &'static str {
+    static BINARY: OnceLock<String> = OnceLock::new();
+    BINARY.get_or_init(|| {
+        std::env::var(RUNTIME_ENV)
+            .ok()
+            .filter(|bin| !bin.trim().is_empty())
+            .unwrap_or_else(|| "docker".to_string())
+    })
+}
+
+/// creates a command running the docker binary.
+pub(crate) fn docker_command() -> Command {
+    Command::new(docker_binary())
+}
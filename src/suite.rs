@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::os::raw::c_int;
+use std::sync::{Mutex, OnceLock};
+
+use crate::{reuse, start_container_with_opts, stop_container, Container, StartOptions};
+
+extern "C" {
+    // from the C runtime std links against, the handlers run when main
+    // returns or std::process::exit is called
+    fn atexit(callback: extern "C" fn()) -> c_int;
+}
+
+/// TestSuite shares containers across the tests of a test binary, starting
+/// each one on first use and stopping them all when the process exits.
+///
+/// The default harness runs every test of a binary in one process, so a
+/// suite container is started once. cargo-nextest runs each test in its own
+/// process, so each test starts its own; set StartOptions::reuse to share one
+/// across the processes too, and remove it with cleanup_orphans afterwards.
+pub struct TestSuite {
+    containers: Mutex<HashMap<String, Container>>,
+}
+
+static SUITE: OnceLock<TestSuite> = OnceLock::new();
+
+impl TestSuite {
+    /// gets the suite of this test binary, the first call registers the hook
+    /// stopping its containers at process exit.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use docker_tester::{StartOptions, TestSuite};
+    ///
+    /// #[test]
+    /// fn it_works() {
+    ///     let redis = TestSuite::global()
+    ///         .shared_container("redis:7-alpine", "6379", &[], StartOptions::default())
+    ///         .unwrap();
+    ///     // every test of the binary gets the same container
+    /// }
+    /// ```
+    pub fn global() -> &'static TestSuite {
+        SUITE.get_or_init(|| {
+            // SAFETY: shutdown_at_exit is a plain function that never unwinds
+            if unsafe { atexit(shutdown_at_exit) } != 0 {
+                warn!("The suite containers won't be stopped at exit, atexit failed");
+            }
+            TestSuite {
+                containers: Mutex::new(HashMap::new()),
+            }
+        })
+    }
+
+    /// starts the container like start_container_with_opts the first time,
+    /// and returns the running one when the same image, port, args and
+    /// options are asked for again. Don't stop it, the suite does.
+    pub fn shared_container(
+        &self,
+        image: &str,
+        port: &str,
+        args: &[&str],
+        opts: StartOptions,
+    ) -> Result<Container, anyhow::Error> {
+        let key = format!("{port}-{}", reuse::reuse_key(image, args, &opts));
+        // the lock is held while starting, so concurrent tests wait for the
+        // first one instead of starting a container each
+        let mut containers = self
+            .containers
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        if let Some(container) = containers.get(&key) {
+            return Ok(container.clone());
+        }
+        let container = start_container_with_opts(image, port, args, opts)?;
+        containers.insert(key, container.clone());
+        Ok(container)
+    }
+
+    /// stops and removes the suite containers now, e.g. at the end of a custom
+    /// harness. It runs again at exit, for the containers started since.
+    pub fn shutdown(&self) {
+        let containers: Vec<Container> = self
+            .containers
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .drain()
+            .map(|(_, container)| container)
+            .collect();
+        for container in containers {
+            match stop_container(container.id.clone()) {
+                Ok(()) => info!("Suite container[{}] dropped", container.id),
+                Err(err) => warn!("Suite container[{}] cannot be stopped: {err}", container.id),
+            }
+        }
+    }
+}
+
+extern "C" fn shutdown_at_exit() {
+    // a panic can't unwind out of the C runtime
+    let _ = std::panic::catch_unwind(|| {
+        if let Some(suite) = SUITE.get() {
+            suite.shutdown();
+        }
+    });
+}
@@ -0,0 +1,291 @@
+use std::process::Output;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::error::docker_error;
+use crate::logs::{follow_logs, LogFollower};
+use crate::runtime::docker_command;
+
+/// DEFAULT_TIMEOUT bounds the readiness wait when no timeout is configured.
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(45);
+
+/// resolves the readiness timeout, an explicit timeout wins over the
+/// `DOCKER_TESTER_TIMEOUT_SECS` env var, which wins over DEFAULT_TIMEOUT.
+pub(crate) fn readiness_timeout(explicit: Option<Duration>) -> Result<Duration, anyhow::Error> {
+    if let Some(timeout) = explicit {
+        return Ok(timeout);
+    }
+    match std::env::var("DOCKER_TESTER_TIMEOUT_SECS") {
+        Ok(secs) => parse_timeout_secs(&secs),
+        Err(_) => Ok(DEFAULT_TIMEOUT),
+    }
+}
+
+fn parse_timeout_secs(secs: &str) -> Result<Duration, anyhow::Error> {
+    secs.trim()
+        .parse::<u64>()
+        .map(Duration::from_secs)
+        .map_err(|_| {
+            anyhow::anyhow!("DOCKER_TESTER_TIMEOUT_SECS must be a number of seconds, got {secs:?}")
+        })
+}
+
+/// Backoff paces a readiness loop: the n-th retry waits n seconds, or a fixed
+/// interval when one is set, until the timeout elapses or the attempts run out.
+pub(crate) struct Backoff {
+    deadline: Instant,
+    attempts: u32,
+    max_attempts: Option<u32>,
+    interval: Option<Duration>,
+}
+
+impl Backoff {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Self {
+            deadline: Instant::now() + timeout,
+            attempts: 1,
+            max_attempts: None,
+            interval: None,
+        }
+    }
+
+    /// stops after max_attempts attempts, even when the timeout didn't elapse.
+    pub(crate) fn max_attempts(mut self, max_attempts: Option<u32>) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// waits interval between the attempts instead of increasing the delay.
+    pub(crate) fn interval(mut self, interval: Option<Duration>) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// the number of attempts made so far.
+    pub(crate) fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// gets the delay before the next attempt, or None once the timeout elapsed.
+    pub(crate) fn next_delay(&mut self) -> Option<Duration> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero()
+            || self
+                .max_attempts
+                .is_some_and(|max_attempts| self.attempts >= max_attempts)
+        {
+            return None;
+        }
+        let delay = self
+            .interval
+            .unwrap_or(Duration::from_secs(self.attempts as u64))
+            .min(remaining);
+        self.attempts += 1;
+        Some(delay)
+    }
+}
+
+/// WaitStrategy decides when a started container is considered ready.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum WaitStrategy {
+    /// the container is ready as soon as docker reports it as running.
+    #[default]
+    Running,
+    /// the container is ready once the file exists inside of it,
+    /// checked with `docker exec <id> test -f <path>`.
+    FileExists(String),
+    /// the container is ready once its healthcheck reports healthy,
+    /// the image or StartOptions::healthcheck must define one.
+    Healthy,
+    /// the container is ready once the command exits with 0 inside of it,
+    /// e.g. `pg_isready` or `redis-cli ping`. The timeout error has its last output.
+    ExecSucceeds(Vec<String>),
+    /// the container is ready once a line of its logs contains the text,
+    /// e.g. `ready to accept connections`, for images that log when they're
+    /// done initializing. See wait_for_log to wait for a line later on.
+    LogLine(String),
+}
+
+/// Readiness checks a WaitStrategy against a started container. For
+/// WaitStrategy::LogLine it follows the logs once in the background, so a
+/// check doesn't read the whole logs of a chatty container again.
+pub(crate) struct Readiness<'a> {
+    strategy: &'a WaitStrategy,
+    /// set once a line with the LogLine text was logged.
+    log_line_seen: Option<(Arc<AtomicBool>, LogFollower)>,
+}
+
+impl<'a> Readiness<'a> {
+    pub(crate) fn new(strategy: &'a WaitStrategy, id: &str) -> Result<Self, anyhow::Error> {
+        let log_line_seen = match strategy {
+            WaitStrategy::LogLine(text) => {
+                let seen = Arc::new(AtomicBool::new(false));
+                let (flag, text) = (seen.clone(), text.clone());
+                let follower = follow_logs(id, move |line| {
+                    if line.contains(text.as_str()) {
+                        flag.store(true, Ordering::SeqCst);
+                    }
+                })?;
+                Some((seen, follower))
+            }
+            _ => None,
+        };
+        Ok(Self {
+            strategy,
+            log_line_seen,
+        })
+    }
+
+    /// checks the strategy once against a running container, and explains
+    /// why it isn't ready yet, None once it's ready.
+    pub(crate) fn not_ready_reason(&self, id: &str) -> Result<Option<String>, anyhow::Error> {
+        let ready = match self.strategy {
+            WaitStrategy::Running => true,
+            WaitStrategy::FileExists(path) => exec_succeeds(id, &["test", "-f", path])?,
+            WaitStrategy::Healthy => {
+                let output = docker_command()
+                    .arg("inspect")
+                    .arg("-f")
+                    .arg("{{if .State.Health}}{{.State.Health.Status}}{{end}}")
+                    .arg(id)
+                    .output()?;
+                if !output.status.success() {
+                    return Err(docker_error(output.stderr));
+                }
+                return health_not_ready_reason(id, &String::from_utf8(output.stdout)?);
+            }
+            WaitStrategy::LogLine(text) => {
+                let seen = self
+                    .log_line_seen
+                    .as_ref()
+                    .is_some_and(|(seen, _)| seen.load(Ordering::SeqCst));
+                if seen {
+                    true
+                } else {
+                    return Ok(Some(format!("no log line contains {text:?} yet")));
+                }
+            }
+            WaitStrategy::ExecSucceeds(cmd) => {
+                let cmd: Vec<&str> = cmd.iter().map(String::as_str).collect();
+                let output = exec_output(id, &cmd)?;
+                if output.status.success() {
+                    true
+                } else {
+                    return Ok(Some(exec_failure(&cmd, &output)));
+                }
+            }
+        };
+        Ok((!ready).then(|| format!("{:?} is not satisfied", self.strategy)))
+    }
+}
+
+/// explains why the `{{.State.Health.Status}}` status isn't healthy, None once
+/// it is. The status is empty without a healthcheck, the start fails and the
+/// container is stopped then, as it would never become healthy.
+fn health_not_ready_reason(id: &str, status: &str) -> Result<Option<String>, anyhow::Error> {
+    match status.trim() {
+        "" => Err(anyhow::anyhow!(
+            "the container[{id}] has no healthcheck, set StartOptions::healthcheck"
+        )),
+        "healthy" => Ok(None),
+        status => Ok(Some(format!("health status {status}"))),
+    }
+}
+
+/// describes a failed exec with its exit status and output.
+fn exec_failure(cmd: &[&str], output: &Output) -> String {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let mut failure = format!("`{}` exited with {}", cmd.join(" "), output.status);
+    for out in [stdout.trim(), stderr.trim()] {
+        if !out.is_empty() {
+            failure.push_str(": ");
+            failure.push_str(out);
+        }
+    }
+    failure
+}
+
+/// runs the command inside the container and tells whether it exited with 0.
+///
+/// The check runs next to the service, so it works even when the mapped port
+/// isn't reachable from the test process, e.g. with a remote docker daemon.
+pub(crate) fn exec_succeeds(id: &str, cmd: &[&str]) -> Result<bool, anyhow::Error> {
+    Ok(exec_output(id, cmd)?.status.success())
+}
+
+/// runs the command inside the container and captures its output.
+pub(crate) fn exec_output(id: &str, cmd: &[&str]) -> Result<Output, anyhow::Error> {
+    Ok(docker_command().arg("exec").arg(id).args(cmd).output()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn health_not_ready_reason_should_reject_a_missing_healthcheck() {
+        // `running ` from the state template, the container has no healthcheck
+        let err = health_not_ready_reason("0123456789ab", " \n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the container[0123456789ab] has no healthcheck, set StartOptions::healthcheck"
+        );
+        assert_eq!(
+            health_not_ready_reason("0123456789ab", "healthy\n").unwrap(),
+            None
+        );
+        assert_eq!(
+            health_not_ready_reason("0123456789ab", "starting").unwrap(),
+            Some("health status starting".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_timeout_secs_should_reject_non_numbers() {
+        assert_eq!(
+            parse_timeout_secs(" 120 ").unwrap(),
+            Duration::from_secs(120)
+        );
+        assert!(parse_timeout_secs("2m").is_err());
+    }
+
+    #[test]
+    fn exec_failure_should_have_the_last_output() {
+        let output = Command::new("sh")
+            .args(["-c", "echo not yet; exit 1"])
+            .output()
+            .unwrap();
+        assert_eq!(
+            exec_failure(&["redis-cli", "ping"], &output),
+            "`redis-cli ping` exited with exit status: 1: not yet"
+        );
+    }
+
+    #[test]
+    fn backoff_should_stop_at_the_timeout() {
+        let mut backoff = Backoff::new(Duration::from_millis(1500));
+        assert_eq!(backoff.next_delay(), Some(Duration::from_secs(1)));
+        assert_eq!(backoff.attempts(), 2);
+        // the second retry would wait 2 seconds, but only the remaining time is left
+        assert!(backoff.next_delay().unwrap() <= Duration::from_millis(1500));
+
+        let mut backoff = Backoff::new(Duration::ZERO);
+        assert_eq!(backoff.next_delay(), None);
+        assert_eq!(backoff.attempts(), 1);
+    }
+
+    #[test]
+    fn backoff_should_stop_after_max_attempts() {
+        let interval = Duration::from_millis(200);
+        let mut backoff = Backoff::new(Duration::from_secs(60))
+            .max_attempts(Some(3))
+            .interval(Some(interval));
+        assert_eq!(backoff.next_delay(), Some(interval));
+        assert_eq!(backoff.next_delay(), Some(interval));
+        assert_eq!(backoff.next_delay(), None);
+        assert_eq!(backoff.attempts(), 3);
+    }
+}